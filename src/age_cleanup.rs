@@ -0,0 +1,494 @@
+use crate::app_dirs;
+use crate::delete;
+use crate::logger;
+use crate::protected;
+use crate::utils;
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use walkdir::WalkDir;
+
+pub const AGE_RULES_FILE: &str = "age_rules.yaml";
+
+// 预览列表中最多显示的文件数
+const PREVIEW_LIMIT: usize = 200;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// 按时间清理的规则：`path` 相对于根目录，`*` 匹配单级目录名中的任意字符
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgeRule {
+    pub root: String,
+    pub path: String,
+    pub days: u64,
+}
+
+impl AgeRule {
+    fn new(root: &str, path: &str, days: u64) -> Self {
+        Self {
+            root: root.to_string(),
+            path: path.to_string(),
+            days,
+        }
+    }
+
+    // 天数过大时按最大值处理，避免换算成秒时溢出
+    fn max_age(&self) -> Duration {
+        Duration::from_secs(self.days.saturating_mul(SECS_PER_DAY))
+    }
+}
+
+fn default_rules() -> Vec<AgeRule> {
+    vec![
+        AgeRule::new("Local", "Temp", 30),
+        AgeRule::new("Roaming", "*/logs", 30),
+        AgeRule::new("Local", "*/logs", 30),
+    ]
+}
+
+pub fn load_age_rules() -> Vec<AgeRule> {
+    let Ok(content) = fs::read_to_string(app_dirs::config_file(AGE_RULES_FILE)) else {
+        return default_rules();
+    };
+    serde_yaml::from_str(&content).unwrap_or_else(|e| {
+        logger::log_error(&format!("解析按时间清理规则失败: {}", e));
+        default_rules()
+    })
+}
+
+pub fn save_age_rules(rules: &[AgeRule]) {
+    match serde_yaml::to_string(rules) {
+        Ok(content) => {
+            if let Err(e) = fs::write(app_dirs::config_file(AGE_RULES_FILE), content) {
+                logger::log_error(&format!("保存按时间清理规则失败: {}", e));
+            }
+        }
+        Err(e) => logger::log_error(&format!("序列化按时间清理规则失败: {}", e)),
+    }
+}
+
+/// 符合规则的过期文件
+#[derive(Debug, Clone)]
+pub struct AgeMatch {
+    pub path: PathBuf,
+    pub size: u64,
+    pub age_days: u64,
+    pub max_age: Duration, // 匹配规则的期限，删除前用于复查
+}
+
+// 普通文件距上次修改的时长，符号链接等其他类型返回 None
+fn file_age(metadata: &fs::Metadata, now: SystemTime) -> Option<Duration> {
+    if !metadata.is_file() {
+        return None;
+    }
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| now.duration_since(modified).ok())
+}
+
+// 删除前复查文件是否仍然过期，预览后被修改过的文件会被保留
+fn is_still_expired(path: &Path, max_age: Duration) -> bool {
+    fs::symlink_metadata(path)
+        .ok()
+        .and_then(|metadata| file_age(&metadata, SystemTime::now()))
+        .is_some_and(|age| age >= max_age)
+}
+
+// 单级目录名匹配，支持 `*` 和 `?`，不区分大小写
+fn segment_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = backtrack {
+            p = star_p + 1;
+            n = star_n + 1;
+            backtrack = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// 按规则路径逐级展开，得到所有匹配的目录。
+// 规则中含有 "." 或 ".." 时不展开，避免清理根目录以外的位置
+fn expand_rule_dirs(base_path: &Path, rule_path: &str) -> Vec<PathBuf> {
+    let segments: Vec<&str> = rule_path
+        .split(['/', '\\'])
+        .filter(|s| !s.is_empty())
+        .collect();
+    if segments.iter().any(|s| *s == "." || *s == "..") {
+        logger::log_error(&format!("忽略无效的按时间清理规则: {}", rule_path));
+        return Vec::new();
+    }
+
+    // 只展开真实的目录，不跟随符号链接
+    let is_real_dir = |path: &Path| {
+        fs::symlink_metadata(path)
+            .map(|m| m.is_dir())
+            .unwrap_or(false)
+    };
+
+    let mut dirs = vec![base_path.to_path_buf()];
+    for segment in segments {
+        let mut next = Vec::new();
+        for dir in &dirs {
+            if segment.contains(['*', '?']) {
+                let Ok(entries) = fs::read_dir(dir) else {
+                    continue;
+                };
+                next.extend(
+                    entries
+                        .flatten()
+                        .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+                        .filter(|e| segment_matches(segment, &e.file_name().to_string_lossy()))
+                        .map(|e| e.path()),
+                );
+            } else if is_real_dir(&dir.join(segment)) {
+                next.push(dir.join(segment));
+            }
+        }
+        dirs = next;
+    }
+    dirs
+}
+
+// 查找一条规则匹配目录中超过期限未修改的文件
+fn find_expired_in(base_path: &Path, rule: &AgeRule, now: SystemTime) -> Vec<AgeMatch> {
+    let max_age = rule.max_age();
+    let mut matches = Vec::new();
+
+    for dir in expand_rule_dirs(base_path, &rule.path) {
+        // 只跳过禁止操作的文件夹。需要确认的文件夹（如 Local/Temp）正是按时间清理的对象，
+        // 这里只删除其中超过期限的文件
+        match protected::check_path(&dir) {
+            Some(entry) if entry.severity == protected::Severity::Blocked => {
+                logger::log_info(&format!(
+                    "文件夹 {} 受保护（{}），不按时间清理",
+                    entry.name, entry.reason
+                ));
+                continue;
+            }
+            _ => {}
+        }
+        for entry in WalkDir::new(&dir).into_iter().filter_map(Result::ok) {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            match file_age(&metadata, now) {
+                Some(age) if age >= max_age => matches.push(AgeMatch {
+                    path: entry.path().to_path_buf(),
+                    size: metadata.len(),
+                    age_days: age.as_secs() / SECS_PER_DAY,
+                    max_age,
+                }),
+                _ => {}
+            }
+        }
+    }
+    matches
+}
+
+/// 查找所有规则匹配目录中超过期限未修改的文件
+pub fn find_expired_files(rules: &[AgeRule]) -> Vec<AgeMatch> {
+    let now = SystemTime::now();
+    let mut matches: Vec<AgeMatch> = Vec::new();
+    let mut seen = HashSet::new();
+
+    for rule in rules {
+        let Some(base_path) = utils::get_appdata_dir(&rule.root) else {
+            continue;
+        };
+        // 多条规则匹配同一文件时只保留一次
+        matches.extend(
+            find_expired_in(&base_path, rule, now)
+                .into_iter()
+                .filter(|m| seen.insert(m.path.clone())),
+        );
+    }
+
+    matches.sort_by_key(|m| std::cmp::Reverse(m.size));
+    matches
+}
+
+enum AgeMessage {
+    Preview(Vec<AgeMatch>),
+    Progress(delete::DeleteProgress),
+    Finished(delete::DeleteReport),
+}
+
+/// "按时间清理"窗口：先预览过期文件，确认后再删除
+#[derive(Default)]
+pub struct AgeCleanup {
+    pub show_window: bool,
+    rules: Vec<AgeRule>,
+    matches: Vec<AgeMatch>,
+    progress: delete::DeleteProgress,
+    status_message: Option<String>,
+    is_busy: bool,
+    rx: Option<Receiver<AgeMessage>>,
+    cancel_flag: Arc<AtomicBool>,
+}
+
+impl AgeCleanup {
+    pub fn open(&mut self) {
+        self.show_window = true;
+        self.rules = load_age_rules();
+        self.matches.clear();
+        self.status_message = None;
+    }
+
+    fn start_preview(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        self.rx = Some(rx);
+        self.matches.clear();
+        self.is_busy = true;
+        self.status_message = Some("正在查找过期文件...".to_string());
+
+        let rules = self.rules.clone();
+        thread::spawn(move || {
+            let _ = tx.send(AgeMessage::Preview(find_expired_files(&rules)));
+        });
+    }
+
+    fn start_delete(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        self.rx = Some(rx);
+        self.is_busy = true;
+        self.progress = delete::DeleteProgress::default();
+        self.status_message = Some("正在删除过期文件...".to_string());
+        self.cancel_flag = Arc::new(AtomicBool::new(false));
+
+        let files: Vec<PathBuf> = self.matches.iter().map(|m| m.path.clone()).collect();
+        let max_ages: HashMap<PathBuf, Duration> = self
+            .matches
+            .iter()
+            .map(|m| (m.path.clone(), m.max_age))
+            .collect();
+        let cancel_flag = Arc::clone(&self.cancel_flag);
+        thread::spawn(move || {
+            let still_expired = |path: &Path| {
+                max_ages
+                    .get(path)
+                    .is_some_and(|max_age| is_still_expired(path, *max_age))
+            };
+            let report =
+                delete::delete_files(&files, false, &cancel_flag, still_expired, |progress| {
+                    let _ = tx.send(AgeMessage::Progress(progress.clone()));
+                });
+            let _ = tx.send(AgeMessage::Finished(report));
+        });
+    }
+
+    fn poll(&mut self) {
+        let Some(rx) = &self.rx else {
+            return;
+        };
+        while let Ok(msg) = rx.try_recv() {
+            match msg {
+                AgeMessage::Preview(matches) => {
+                    let total: u64 = matches.iter().map(|m| m.size).sum();
+                    self.status_message = Some(format!(
+                        "找到 {} 个过期文件，共 {}",
+                        utils::format_count(matches.len() as u64),
+                        utils::format_size(total)
+                    ));
+                    self.matches = matches;
+                    self.is_busy = false;
+                }
+                AgeMessage::Progress(progress) => self.progress = progress,
+                AgeMessage::Finished(report) => {
+                    let mut message = report.summary();
+                    if report.cancelled {
+                        message = format!("已取消，{}", message);
+                    }
+                    self.status_message = Some(message);
+                    self.matches.clear();
+                    self.is_busy = false;
+                }
+            }
+        }
+    }
+
+    pub fn show_age_window(&mut self, ctx: &egui::Context) {
+        if !self.show_window {
+            return;
+        }
+        self.poll();
+        if self.is_busy {
+            ctx.request_repaint();
+        }
+
+        let mut open = true;
+        egui::Window::new("按时间清理")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label("删除规则目录中超过指定天数未修改的文件");
+
+                // 规则列表，天数可直接修改
+                let mut rules_changed = false;
+                egui::Grid::new("age_rules").striped(true).show(ui, |ui| {
+                    for rule in &mut self.rules {
+                        ui.label(format!("{}/{}", rule.root, rule.path));
+                        let response = ui.add_enabled(
+                            !self.is_busy,
+                            egui::DragValue::new(&mut rule.days).range(1..=3650),
+                        );
+                        rules_changed |= response.changed();
+                        ui.label("天");
+                        ui.end_row();
+                    }
+                });
+                if rules_changed {
+                    save_age_rules(&self.rules);
+                    self.matches.clear();
+                }
+
+                if !self.matches.is_empty() {
+                    ui.separator();
+                    egui::ScrollArea::vertical()
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            egui::Grid::new("age_matches").striped(true).show(ui, |ui| {
+                                for item in self.matches.iter().take(PREVIEW_LIMIT) {
+                                    ui.label(item.path.display().to_string());
+                                    ui.label(utils::format_size(item.size));
+                                    ui.label(format!("{} 天前", item.age_days));
+                                    ui.end_row();
+                                }
+                            });
+                            if self.matches.len() > PREVIEW_LIMIT {
+                                ui.label(format!(
+                                    "…… 另有 {} 个文件",
+                                    self.matches.len() - PREVIEW_LIMIT
+                                ));
+                            }
+                        });
+                }
+
+                if self.is_busy && self.progress.files_removed > 0 {
+                    ui.label(format!(
+                        "已删除 {} 个文件，释放 {}",
+                        utils::format_count(self.progress.files_removed),
+                        utils::format_size(self.progress.bytes_freed)
+                    ));
+                }
+                if let Some(message) = &self.status_message {
+                    ui.label(message);
+                }
+
+                ui.horizontal(|ui| {
+                    if self.is_busy {
+                        if !self.matches.is_empty() && ui.button("取消").clicked() {
+                            self.cancel_flag.store(true, Ordering::Relaxed);
+                        }
+                    } else {
+                        if ui.button("预览").clicked() {
+                            self.start_preview();
+                        }
+                        if !self.matches.is_empty() && ui.button("删除这些文件").clicked() {
+                            self.start_delete();
+                        }
+                    }
+                });
+            });
+
+        if !open {
+            self.cancel_flag.store(true, Ordering::Relaxed);
+            self.show_window = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_matching_supports_wildcards_case_insensitively() {
+        assert!(segment_matches("*", "anything"));
+        assert!(segment_matches("logs", "Logs"));
+        assert!(segment_matches("*cache*", "GPUCache_old"));
+        assert!(segment_matches("app?", "App1"));
+        assert!(segment_matches("a*b*c", "aXbYbZc"));
+        assert!(!segment_matches("app?", "App"));
+        assert!(!segment_matches("*.log", "log.txt"));
+        assert!(!segment_matches("logs", "logs2"));
+    }
+
+    #[test]
+    fn finds_only_files_older_than_the_rule_in_matching_dirs() {
+        let base = std::env::temp_dir().join(format!("adc_age_cleanup_{}", std::process::id()));
+        let now = SystemTime::now();
+        let days_ago = |days: u64| {
+            filetime::FileTime::from_system_time(now - Duration::from_secs(days * SECS_PER_DAY))
+        };
+        for (path, days) in [
+            ("App/logs/old.log", 40),
+            ("App/logs/new.log", 1),
+            ("App/data/old.log", 40),
+            ("outside.log", 40),
+        ] {
+            let path = base.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "x").unwrap();
+            filetime::set_file_mtime(&path, days_ago(days)).unwrap();
+        }
+
+        let found = find_expired_in(&base, &AgeRule::new("Local", "*/logs", 30), now);
+        let paths: Vec<&Path> = found.iter().map(|m| m.path.as_path()).collect();
+        assert_eq!(paths, [base.join("App/logs/old.log")]);
+        assert_eq!(found[0].age_days, 40);
+
+        // 天数过大时不会溢出，也不会匹配任何文件
+        assert!(find_expired_in(&base, &AgeRule::new("Local", "*/logs", u64::MAX), now).is_empty());
+        // 规则不能跳出根目录
+        assert!(expand_rule_dirs(&base.join("App/logs"), "../..").is_empty());
+
+        // 预览后被修改的文件在删除前复查时会被保留
+        let old = base.join("App/logs/old.log");
+        assert!(is_still_expired(&old, found[0].max_age));
+        fs::write(&old, "modified").unwrap();
+        assert!(!is_still_expired(&old, found[0].max_age));
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn default_rules_find_old_files_in_temp() {
+        let base = std::env::temp_dir().join(format!("adc_age_defaults_{}", std::process::id()));
+        let now = SystemTime::now();
+        let old = base.join("Local/Temp/setup/old.tmp");
+        fs::create_dir_all(old.parent().unwrap()).unwrap();
+        fs::write(&old, "x").unwrap();
+        let mtime = now - Duration::from_secs(40 * SECS_PER_DAY);
+        filetime::set_file_mtime(&old, filetime::FileTime::from_system_time(mtime)).unwrap();
+
+        let found: Vec<PathBuf> = default_rules()
+            .iter()
+            .flat_map(|rule| find_expired_in(&base.join(&rule.root), rule, now))
+            .map(|m| m.path)
+            .collect();
+        assert_eq!(found, [old]);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
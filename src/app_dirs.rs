@@ -0,0 +1,252 @@
+use crate::age_cleanup::AGE_RULES_FILE;
+use crate::content_cleaner::CLEAN_RULES_FILE;
+use crate::ignore::{IGNORE_FILE, LEGACY_IGNORE_FILE};
+use crate::logger::{self, AUDIT_LOG_FILE, LOG_FILE};
+use crate::move_checkpoint::MOVE_CHECKPOINT_FILE;
+use crate::move_journal::MOVE_JOURNAL_FILE;
+use crate::protected::PROTECTED_FILE;
+use crate::relocation::RELOCATIONS_FILE;
+use crate::yaml_loader::DESCRIPTIONS_FILE;
+use dirs_next as dirs;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 与程序放在同一目录时进入便携模式，所有文件都保存在程序目录中
+pub const PORTABLE_MARKER: &str = "portable.txt";
+
+// 旧版本保存在工作目录中的文件，启动时移动到新的位置
+const CONFIG_FILES: [&str; 10] = [
+    DESCRIPTIONS_FILE,
+    IGNORE_FILE,
+    LEGACY_IGNORE_FILE,
+    PROTECTED_FILE,
+    CLEAN_RULES_FILE,
+    AGE_RULES_FILE,
+    RELOCATIONS_FILE,
+    MOVE_JOURNAL_FILE,
+    MOVE_CHECKPOINT_FILE,
+    "adcignore.txt.bak",
+];
+const LOG_FILES: [&str; 2] = [LOG_FILE, AUDIT_LOG_FILE];
+
+/// 程序使用的目录：配置和记录、日志、缓存
+pub struct AppDirs {
+    pub config: PathBuf,
+    pub logs: PathBuf,
+    pub cache: PathBuf,
+    pub portable: bool,
+}
+
+#[cfg(not(test))]
+pub fn app_dirs() -> &'static AppDirs {
+    static APP_DIRS: std::sync::OnceLock<AppDirs> = std::sync::OnceLock::new();
+    APP_DIRS.get_or_init(|| create_dirs(resolve()))
+}
+
+// 测试不能读写用户的配置目录。每个测试在自己的线程中运行，
+// 使用各自的临时目录，线程结束时删除
+#[cfg(test)]
+pub fn app_dirs() -> &'static AppDirs {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct TestDirs {
+        base: PathBuf,
+        dirs: &'static AppDirs,
+    }
+
+    impl Drop for TestDirs {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.base);
+        }
+    }
+
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+        static TEST_DIRS: TestDirs = {
+            let base = std::env::temp_dir().join(format!(
+                "adc_app_dirs_{}_{}",
+                std::process::id(),
+                NEXT_ID.fetch_add(1, Ordering::Relaxed)
+            ));
+            let dirs = Box::leak(Box::new(create_dirs(resolve_in(&base))));
+            TestDirs { base, dirs }
+        };
+    }
+    TEST_DIRS.with(|test_dirs| test_dirs.dirs)
+}
+
+fn create_dirs(app_dirs: AppDirs) -> AppDirs {
+    for dir in [&app_dirs.config, &app_dirs.logs, &app_dirs.cache] {
+        if let Err(e) = fs::create_dir_all(dir) {
+            logger::log_error(&format!("无法创建目录 {}: {}", dir.display(), e));
+        }
+    }
+    app_dirs
+}
+
+fn exe_dir() -> Option<PathBuf> {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+}
+
+// Windows 上为 %APPDATA%\AppDataCleaner 和 %LOCALAPPDATA%\AppDataCleaner，
+// Linux 上为 $XDG_CONFIG_HOME/appdatacleaner、$XDG_DATA_HOME/appdatacleaner 和 $XDG_CACHE_HOME/appdatacleaner
+fn resolve() -> AppDirs {
+    if let Some(dir) = exe_dir().filter(|dir| dir.join(PORTABLE_MARKER).exists()) {
+        return AppDirs {
+            config: dir.clone(),
+            logs: dir.clone(),
+            cache: dir.join("cache"),
+            portable: true,
+        };
+    }
+
+    let name = if cfg!(any(windows, target_os = "macos")) {
+        "AppDataCleaner"
+    } else {
+        "appdatacleaner"
+    };
+    // 找不到用户目录时退回到程序目录
+    let fallback = exe_dir().unwrap_or_else(|| PathBuf::from("."));
+    let dir = |base: Option<PathBuf>| base.unwrap_or_else(|| fallback.clone()).join(name);
+    AppDirs {
+        config: dir(dirs::config_dir()),
+        logs: dir(dirs::data_local_dir()).join("logs"),
+        cache: dir(dirs::cache_dir()).join("cache"),
+        portable: false,
+    }
+}
+
+// 所有目录都放在 `base` 下
+#[cfg(test)]
+fn resolve_in(base: &Path) -> AppDirs {
+    AppDirs {
+        config: base.join("config"),
+        logs: base.join("logs"),
+        cache: base.join("cache"),
+        portable: false,
+    }
+}
+
+/// 配置目录中的文件
+pub fn config_file(name: &str) -> PathBuf {
+    app_dirs().config.join(name)
+}
+
+/// 日志目录中的文件
+pub fn log_file(name: &str) -> PathBuf {
+    app_dirs().logs.join(name)
+}
+
+/// 把旧版本留在工作目录中的文件移动到配置目录和日志目录，已存在的文件不覆盖。
+/// 日志文件在初始化日志系统时创建，所以迁移要在那之前进行，迁移的记录由调用方在初始化后写入日志
+pub fn migrate_working_dir_files() -> Vec<String> {
+    let Ok(working_dir) = std::env::current_dir() else {
+        return Vec::new();
+    };
+    let app_dirs = app_dirs();
+    let mut notes = migrate_files(&working_dir, &app_dirs.config, &CONFIG_FILES);
+    notes.extend(migrate_files(&working_dir, &app_dirs.logs, &LOG_FILES));
+    notes
+}
+
+fn migrate_files(from: &Path, to: &Path, names: &[&str]) -> Vec<String> {
+    let mut notes = Vec::new();
+    // 便携模式下从程序目录启动时两者相同
+    if fs::canonicalize(from).ok() == fs::canonicalize(to).ok() {
+        return notes;
+    }
+    for name in names {
+        let source = from.join(name);
+        let target = to.join(name);
+        if !source.is_file() {
+            continue;
+        }
+        if target.exists() {
+            notes.push(format!(
+                "{} 已存在，保留工作目录中的 {}",
+                target.display(),
+                source.display()
+            ));
+            continue;
+        }
+        notes.push(match move_file(&source, &target) {
+            Ok(()) => format!("已将 {} 迁移到 {}", source.display(), target.display()),
+            Err(e) => format!("无法迁移 {}: {}", source.display(), e),
+        });
+    }
+    notes
+}
+
+/// 配置文件无法解析时改名为 `<文件名>.bak` 保留，之后保存时不会用空列表覆盖用户原来的内容
+pub fn set_aside_unparsable(path: &Path) {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    let backup = PathBuf::from(backup);
+    match fs::rename(path, &backup) {
+        Ok(()) => logger::log_error(&format!(
+            "{} 无法解析，已改名为 {}，修正后改回原名即可恢复",
+            path.display(),
+            backup.display()
+        )),
+        Err(e) => logger::log_error(&format!("无法备份 {}: {}", path.display(), e)),
+    }
+}
+
+// 跨卷时无法重命名，复制后删除
+fn move_file(source: &Path, target: &Path) -> std::io::Result<()> {
+    if fs::rename(source, target).is_ok() {
+        return Ok(());
+    }
+    fs::copy(source, target)?;
+    fs::remove_file(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_dirs_are_outside_the_working_dir() {
+        let working_dir = std::env::current_dir().unwrap();
+        let dirs = resolve();
+        assert!(!dirs.portable);
+        for dir in [&dirs.config, &dirs.logs, &dirs.cache] {
+            assert!(dir.is_absolute(), "{}", dir.display());
+            assert!(!dir.starts_with(&working_dir), "{}", dir.display());
+            assert!(
+                dir.iter().any(|c| c.eq_ignore_ascii_case("appdatacleaner")),
+                "{}",
+                dir.display()
+            );
+        }
+    }
+
+    #[test]
+    fn working_dir_files_are_moved_without_overwriting() {
+        let base = std::env::temp_dir().join(format!("adc_migrate_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let (from, to) = (base.join("cwd"), base.join("config"));
+        fs::create_dir_all(&from).unwrap();
+        fs::create_dir_all(&to).unwrap();
+        fs::write(from.join(RELOCATIONS_FILE), "old").unwrap();
+        fs::write(from.join(AGE_RULES_FILE), "old").unwrap();
+        fs::write(to.join(AGE_RULES_FILE), "new").unwrap();
+
+        let notes = migrate_files(&from, &to, &CONFIG_FILES);
+        assert_eq!(notes.len(), 2);
+
+        assert!(!from.join(RELOCATIONS_FILE).exists());
+        assert_eq!(
+            fs::read_to_string(to.join(RELOCATIONS_FILE)).unwrap(),
+            "old"
+        );
+        assert_eq!(
+            fs::read_to_string(from.join(AGE_RULES_FILE)).unwrap(),
+            "old"
+        );
+        assert_eq!(fs::read_to_string(to.join(AGE_RULES_FILE)).unwrap(), "new");
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
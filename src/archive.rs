@@ -0,0 +1,647 @@
+use crate::delete;
+use crate::logger;
+use crate::process_check;
+use crate::protected;
+use crate::utils;
+use eframe::egui;
+use native_dialog::FileDialog;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use walkdir::WalkDir;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+pub const MANIFEST_NAME: &str = "manifest.yaml";
+pub const DATA_PREFIX: &str = "data/";
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// 压缩包中的单个文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String, // 相对于原文件夹的路径，使用 / 分隔
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// 压缩包清单，记录原始位置以便之后还原
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub version: u32,
+    pub root: String,        // Roaming / Local / LocalLow
+    pub folder_name: String, // 根目录下的文件夹名
+    pub original_path: PathBuf,
+    pub created: String,
+    pub directories: Vec<String>, // 包括空目录在内的所有子目录
+    pub files: Vec<ManifestEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<String>, // 无法备份的符号链接和特殊文件
+}
+
+impl ArchiveManifest {
+    pub fn total_size(&self) -> u64 {
+        self.files.iter().map(|f| f.size).sum()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveProgress {
+    pub stage: String,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub current_path: String,
+}
+
+impl ArchiveProgress {
+    pub fn fraction(&self) -> f32 {
+        if self.bytes_total == 0 {
+            0.0
+        } else {
+            self.bytes_done as f32 / self.bytes_total as f32
+        }
+    }
+}
+
+fn relative_name(base: &Path, path: &Path) -> String {
+    path.strip_prefix(base)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// 把文件夹打包为 .zip，并在压缩包中写入清单。
+/// 取消或失败时会删除未完成的压缩包
+pub fn archive_folder(
+    root: &str,
+    folder_name: &str,
+    source: &Path,
+    archive_path: &Path,
+    cancel_flag: &AtomicBool,
+    mut on_progress: impl FnMut(&ArchiveProgress),
+) -> Result<ArchiveManifest, String> {
+    let result = write_archive(
+        root,
+        folder_name,
+        source,
+        archive_path,
+        cancel_flag,
+        &mut on_progress,
+    );
+    if result.is_err() {
+        let _ = fs::remove_file(archive_path);
+    }
+    result
+}
+
+fn write_archive(
+    root: &str,
+    folder_name: &str,
+    source: &Path,
+    archive_path: &Path,
+    cancel_flag: &AtomicBool,
+    on_progress: &mut impl FnMut(&ArchiveProgress),
+) -> Result<ArchiveManifest, String> {
+    let mut manifest = ArchiveManifest {
+        version: MANIFEST_VERSION,
+        root: root.to_string(),
+        folder_name: folder_name.to_string(),
+        original_path: source.to_path_buf(),
+        created: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        directories: Vec::new(),
+        files: Vec::new(),
+        skipped: Vec::new(),
+    };
+
+    // 先统计所有条目，得到总大小
+    let mut files = Vec::new();
+    for entry in WalkDir::new(source).min_depth(1) {
+        let entry = entry.map_err(|e| format!("无法读取条目: {}", e))?;
+        let name = relative_name(source, entry.path());
+        if entry.file_type().is_dir() {
+            manifest.directories.push(name);
+        } else if entry.file_type().is_file() {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            files.push((entry.path().to_path_buf(), name, size));
+        } else {
+            logger::log_info(&format!("无法备份非普通文件: {}", entry.path().display()));
+            manifest.skipped.push(name);
+        }
+    }
+
+    let mut progress = ArchiveProgress {
+        stage: "正在压缩".to_string(),
+        bytes_total: files.iter().map(|(_, _, size)| size).sum(),
+        ..Default::default()
+    };
+
+    let file = File::create(archive_path).map_err(|e| format!("无法创建压缩包: {}", e))?;
+    let mut writer = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for dir in &manifest.directories {
+        writer
+            .add_directory(format!("{}{}", DATA_PREFIX, dir), options)
+            .map_err(|e| format!("写入目录失败: {} - {}", dir, e))?;
+    }
+
+    for (path, name, size) in files {
+        progress.current_path = name.clone();
+
+        writer
+            .start_file(
+                format!("{}{}", DATA_PREFIX, name),
+                options.large_file(size >= u32::MAX as u64),
+            )
+            .map_err(|e| format!("写入文件失败: {} - {}", name, e))?;
+
+        // 写入的同时计算哈希，每个文件只读取一次
+        let input = File::open(&path).map_err(|e| format!("无法读取文件: {} - {}", name, e))?;
+        let sha256 = utils::hash_reader_with_progress(input, cancel_flag, |chunk| {
+            writer.write_all(chunk)?;
+            progress.bytes_done += chunk.len() as u64;
+            on_progress(&progress);
+            Ok(())
+        })
+        .map_err(|e| format!("备份文件失败: {} - {}", name, e))?
+        .ok_or_else(|| "已取消备份".to_string())?;
+
+        manifest.files.push(ManifestEntry {
+            path: name,
+            size,
+            sha256,
+        });
+    }
+
+    let manifest_yaml =
+        serde_yaml::to_string(&manifest).map_err(|e| format!("生成清单失败: {}", e))?;
+    writer
+        .start_file(MANIFEST_NAME, options)
+        .and_then(|_| Ok(writer.write_all(manifest_yaml.as_bytes())?))
+        .map_err(|e| format!("写入清单失败: {}", e))?;
+    writer
+        .finish()
+        .map_err(|e| format!("写入压缩包失败: {}", e))?;
+
+    Ok(manifest)
+}
+
+/// 读取压缩包中的清单
+pub fn read_manifest(archive_path: &Path) -> Result<ArchiveManifest, String> {
+    let file = File::open(archive_path).map_err(|e| format!("无法打开压缩包: {}", e))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| format!("不是有效的压缩包: {}", e))?;
+    let entry = archive
+        .by_name(MANIFEST_NAME)
+        .map_err(|_| "压缩包中没有清单，不是本工具创建的备份".to_string())?;
+    serde_yaml::from_reader(entry).map_err(|e| format!("解析清单失败: {}", e))
+}
+
+/// 按清单逐个校验压缩包中文件的 SHA-256，返回校验通过的清单。
+/// `cancel_flag` 置位后在下一个数据块前停止
+pub fn verify_archive(
+    archive_path: &Path,
+    cancel_flag: &AtomicBool,
+    mut on_progress: impl FnMut(&ArchiveProgress),
+) -> Result<ArchiveManifest, String> {
+    let manifest = read_manifest(archive_path)?;
+    let file = File::open(archive_path).map_err(|e| format!("无法打开压缩包: {}", e))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| format!("不是有效的压缩包: {}", e))?;
+
+    let mut progress = ArchiveProgress {
+        stage: "正在校验".to_string(),
+        bytes_total: manifest.total_size(),
+        ..Default::default()
+    };
+
+    for entry in &manifest.files {
+        progress.current_path = entry.path.clone();
+        let data = archive
+            .by_name(&format!("{}{}", DATA_PREFIX, entry.path))
+            .map_err(|_| format!("压缩包中缺少文件: {}", entry.path))?;
+        let sha256 = utils::hash_reader_with_progress(data, cancel_flag, |chunk| {
+            progress.bytes_done += chunk.len() as u64;
+            on_progress(&progress);
+            Ok(())
+        })
+        .map_err(|e| format!("读取失败: {} - {}", entry.path, e))?
+        .ok_or_else(|| "已取消校验".to_string())?;
+        if sha256 != entry.sha256 {
+            return Err(format!("校验失败，文件已损坏: {}", entry.path));
+        }
+    }
+
+    Ok(manifest)
+}
+
+enum ArchiveMessage {
+    Progress(ArchiveProgress),
+    Finished(Result<String, String>),
+}
+
+// 保存位置不能在要备份的文件夹内，否则压缩包会被打包进自身，并随原文件夹一起被删除
+fn ensure_target_outside(source: &Path, target_dir: &Path) -> Result<(), String> {
+    let source = fs::canonicalize(source).map_err(|e| format!("无法访问要备份的文件夹: {}", e))?;
+    let target = fs::canonicalize(target_dir).map_err(|e| format!("无法访问保存位置: {}", e))?;
+    if target.starts_with(&source) {
+        return Err(format!(
+            "保存位置 {} 位于要备份的文件夹内，请选择其他位置",
+            target_dir.display()
+        ));
+    }
+    Ok(())
+}
+
+// 备份 → 校验 → 删除原文件夹。`confirmed` 表示用户已输入名称确认删除受保护的文件夹
+fn archive_then_delete(
+    root: &str,
+    folder_name: &str,
+    source: &Path,
+    archive_path: &Path,
+    confirmed: bool,
+    cancel_flag: &AtomicBool,
+    tx: &Sender<ArchiveMessage>,
+) -> Result<String, String> {
+    ensure_target_outside(source, archive_path.parent().unwrap_or(archive_path))?;
+    // 受保护的文件夹在备份前就拒绝，避免白白打包
+    protected::ensure_allowed(source, "删除", confirmed)?;
+
+    let send_progress = |progress: &ArchiveProgress| {
+        let _ = tx.send(ArchiveMessage::Progress(progress.clone()));
+    };
+
+    let manifest = archive_folder(
+        root,
+        folder_name,
+        source,
+        archive_path,
+        cancel_flag,
+        send_progress,
+    )?;
+    verify_archive(archive_path, cancel_flag, send_progress)?;
+
+    let archive_size = fs::metadata(archive_path).map(|m| m.len()).unwrap_or(0);
+    logger::log_audit(&format!(
+        "已备份 {} 到 {}（{} 个文件，原大小 {}，压缩后 {}）",
+        source.display(),
+        archive_path.display(),
+        manifest.files.len(),
+        utils::format_size(manifest.total_size()),
+        utils::format_size(archive_size)
+    ));
+
+    // 没有备份到的内容会随原文件夹一起丢失，这时只保留备份、不删除
+    if !manifest.skipped.is_empty() {
+        return Err(format!(
+            "备份已保存到 {}，但其中 {} 个符号链接或特殊文件无法备份（{}），原文件夹未删除",
+            archive_path.display(),
+            manifest.skipped.len(),
+            manifest.skipped.join("、")
+        ));
+    }
+    if cancel_flag.load(Ordering::Relaxed) {
+        return Err(format!(
+            "已取消，备份已保存到 {}，原文件夹未删除",
+            archive_path.display()
+        ));
+    }
+
+    send_progress(&ArchiveProgress {
+        stage: "正在删除原文件夹".to_string(),
+        ..Default::default()
+    });
+    let options = delete::DeleteOptions {
+        allow_protected: confirmed,
+        ..Default::default()
+    };
+    let report = delete::delete_folder(source, options, cancel_flag, |_| {})?;
+    if !report.is_complete() {
+        return Err(format!(
+            "备份已保存到 {}，但原文件夹未能完全删除：{}",
+            archive_path.display(),
+            report.summary()
+        ));
+    }
+
+    Ok(format!(
+        "已备份到 {}（压缩后 {}），原文件夹已删除",
+        archive_path.display(),
+        utils::format_size(archive_size)
+    ))
+}
+
+/// "备份后删除"窗口
+#[derive(Default)]
+pub struct ArchiveModule {
+    pub show_window: bool,
+    root: String,
+    folder_name: String,
+    target_dir: Option<PathBuf>,
+    progress: ArchiveProgress,
+    status_message: Option<String>,
+    is_busy: bool,
+    rx: Option<Receiver<ArchiveMessage>>,
+    cancel_flag: Arc<AtomicBool>,
+    process_scan: Option<process_check::ProcessCheck>,
+    pending_protection: Option<protected::ProtectedEntry>, // 等待输入名称确认的受保护文件夹
+    protection_confirmed: bool,                            // 已通过输入名称确认删除当前文件夹
+    deleted_folder: Option<String>,                        // 已删除、待从列表中移除的文件夹
+}
+
+impl ArchiveModule {
+    pub fn open(&mut self, root: &str, folder_name: &str) {
+        if self.is_busy {
+            return;
+        }
+        self.show_window = true;
+        self.root = root.to_string();
+        self.folder_name = folder_name.to_string();
+        self.progress = ArchiveProgress::default();
+        self.status_message = None;
+        self.protection_confirmed = false;
+    }
+
+    /// 取出已删除的文件夹名，用于更新主界面列表
+    pub fn take_deleted(&mut self) -> Option<String> {
+        self.deleted_folder.take()
+    }
+
+    fn source_path(&self) -> Option<PathBuf> {
+        utils::get_appdata_dir(&self.root).map(|base| base.join(&self.folder_name))
+    }
+
+    // 依次检查保护级别和占用情况，都通过后才开始备份
+    fn request_start(&mut self) {
+        let (Some(source), Some(target_dir)) = (self.source_path(), self.target_dir.as_deref())
+        else {
+            return;
+        };
+        if let Err(err) = ensure_target_outside(&source, target_dir) {
+            self.status_message = Some(err);
+            return;
+        }
+        if !self.protection_confirmed {
+            match protected::check_path(&source) {
+                Some(entry) if entry.severity == protected::Severity::Blocked => {
+                    self.status_message = Some(format!(
+                        "文件夹 {} 受保护（{}），禁止删除",
+                        self.folder_name, entry.reason
+                    ));
+                    return;
+                }
+                Some(entry) => {
+                    self.pending_protection = Some(entry);
+                    return;
+                }
+                None => {}
+            }
+        }
+        self.process_scan = Some(process_check::ProcessCheck::start(source, "删除"));
+    }
+
+    fn start(&mut self) {
+        let (Some(source), Some(target_dir)) = (self.source_path(), self.target_dir.clone()) else {
+            return;
+        };
+        let archive_path = target_dir.join(format!(
+            "{}_{}_{}.zip",
+            self.root,
+            self.folder_name,
+            chrono::Local::now().format("%Y%m%d_%H%M%S")
+        ));
+
+        let (tx, rx) = mpsc::channel();
+        self.rx = Some(rx);
+        self.is_busy = true;
+        self.status_message = None;
+        self.cancel_flag = Arc::new(AtomicBool::new(false));
+
+        let root = self.root.clone();
+        let folder_name = self.folder_name.clone();
+        let confirmed = self.protection_confirmed;
+        let cancel_flag = Arc::clone(&self.cancel_flag);
+        thread::spawn(move || {
+            let result = archive_then_delete(
+                &root,
+                &folder_name,
+                &source,
+                &archive_path,
+                confirmed,
+                &cancel_flag,
+                &tx,
+            );
+            let _ = tx.send(ArchiveMessage::Finished(result));
+        });
+    }
+
+    fn poll(&mut self) {
+        let Some(rx) = &self.rx else {
+            return;
+        };
+        while let Ok(msg) = rx.try_recv() {
+            match msg {
+                ArchiveMessage::Progress(progress) => self.progress = progress,
+                ArchiveMessage::Finished(result) => {
+                    self.is_busy = false;
+                    match result {
+                        Ok(message) => {
+                            self.deleted_folder = Some(self.folder_name.clone());
+                            self.status_message = Some(message);
+                        }
+                        Err(err) => {
+                            logger::log_error(&err);
+                            self.status_message = Some(err);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn show_archive_window(&mut self, ctx: &egui::Context) {
+        if !self.show_window {
+            return;
+        }
+        self.poll();
+        if self.is_busy {
+            ctx.request_repaint();
+        }
+
+        egui::Window::new("备份后删除")
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(format!("需要备份的文件夹: {}", self.folder_name));
+                ui.label("先压缩为 .zip 并校验，成功后再删除原文件夹");
+
+                ui.horizontal(|ui| {
+                    ui.label("保存位置:");
+                    if let Some(path) = &self.target_dir {
+                        ui.label(path.display().to_string());
+                    }
+                    if ui
+                        .add_enabled(!self.is_busy, egui::Button::new("选择保存位置"))
+                        .clicked()
+                    {
+                        if let Ok(Some(path)) = FileDialog::new().show_open_single_dir() {
+                            self.target_dir = Some(path);
+                        }
+                    }
+                });
+
+                if self.is_busy {
+                    ui.label(format!(
+                        "{}: {}",
+                        self.progress.stage, self.progress.current_path
+                    ));
+                    ui.add(egui::ProgressBar::new(self.progress.fraction()).show_percentage());
+                }
+                if let Some(message) = &self.status_message {
+                    ui.label(message);
+                }
+
+                ui.horizontal(|ui| {
+                    if self.is_busy {
+                        if ui.button("取消").clicked() {
+                            self.cancel_flag.store(true, Ordering::Relaxed);
+                        }
+                    } else {
+                        if ui.button("开始").clicked() {
+                            if self.target_dir.is_some() {
+                                self.request_start();
+                            } else {
+                                self.status_message = Some("请选择保存位置".to_string());
+                            }
+                        }
+                        if ui.button("关闭").clicked() {
+                            self.show_window = false;
+                        }
+                    }
+                });
+            });
+
+        // 受保护的文件夹需要输入名称确认
+        if let Some(entry) = &self.pending_protection {
+            if let Some(confirm) = protected::show_typed_confirmation(ctx, "删除", entry) {
+                self.pending_protection = None;
+                if confirm {
+                    self.protection_confirmed = true;
+                    self.request_start();
+                } else {
+                    self.status_message = Some("已中止备份后删除".to_string());
+                }
+            }
+        }
+
+        if let Some(scan) = &mut self.process_scan {
+            if let Some(proceed) = scan.show(ctx) {
+                self.process_scan = None;
+                if proceed {
+                    self.start();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::restore::{self, ConflictMode};
+
+    #[test]
+    fn archive_verify_and_restore_round_trip() {
+        let dir = std::env::temp_dir().join(format!("adc_archive_{}", std::process::id()));
+        let source = dir.join("App");
+        fs::create_dir_all(source.join("nested/deeper")).unwrap();
+        fs::create_dir_all(source.join("empty")).unwrap();
+        fs::write(source.join("top.txt"), "top").unwrap();
+        fs::write(source.join("nested/deeper/data.bin"), vec![7u8; 300_000]).unwrap();
+
+        let archive_path = dir.join("App.zip");
+        let cancel = AtomicBool::new(false);
+        let manifest =
+            archive_folder("Roaming", "App", &source, &archive_path, &cancel, |_| {}).unwrap();
+        assert_eq!(manifest.files.len(), 2);
+        assert!(manifest.skipped.is_empty());
+        assert_eq!(
+            manifest
+                .files
+                .iter()
+                .find(|f| f.path == "top.txt")
+                .unwrap()
+                .sha256,
+            utils::hash_file(&source.join("top.txt")).unwrap()
+        );
+
+        verify_archive(&archive_path, &cancel, |_| {}).unwrap();
+        assert!(verify_archive(&archive_path, &AtomicBool::new(true), |_| {}).is_err());
+
+        let restored = dir.join("restored");
+        let report = restore::restore_archive(
+            &archive_path,
+            &restored,
+            ConflictMode::Skip,
+            &cancel,
+            |_| {},
+        )
+        .unwrap();
+        assert_eq!(report.files_restored, 2);
+        assert!(utils::compare_dirs_hash(&source, &restored, &cancel)
+            .unwrap()
+            .is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn archive_inside_source_is_rejected_before_deleting() {
+        let dir = std::env::temp_dir().join(format!("adc_archive_inside_{}", std::process::id()));
+        let source = dir.join("App");
+        fs::create_dir_all(source.join("backups")).unwrap();
+        fs::write(source.join("file.txt"), "data").unwrap();
+
+        let archive_path = source.join("backups/App.zip");
+        let (tx, _rx) = mpsc::channel();
+        let err = archive_then_delete(
+            "Roaming",
+            "App",
+            &source,
+            &archive_path,
+            false,
+            &AtomicBool::new(false),
+            &tx,
+        )
+        .unwrap_err();
+        assert!(err.contains("位于要备份的文件夹内"), "{}", err);
+        assert!(!archive_path.exists());
+        assert_eq!(fs::read_to_string(source.join("file.txt")).unwrap(), "data");
+        assert!(ensure_target_outside(&source, &dir).is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_recorded_as_skipped() {
+        let dir = std::env::temp_dir().join(format!("adc_archive_link_{}", std::process::id()));
+        let source = dir.join("App");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("file.txt"), "data").unwrap();
+        std::os::unix::fs::symlink("file.txt", source.join("link")).unwrap();
+
+        let archive_path = dir.join("App.zip");
+        let cancel = AtomicBool::new(false);
+        let manifest =
+            archive_folder("Roaming", "App", &source, &archive_path, &cancel, |_| {}).unwrap();
+        assert_eq!(manifest.skipped, ["link"]);
+        assert_eq!(read_manifest(&archive_path).unwrap().skipped, ["link"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
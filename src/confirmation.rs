@@ -1,9 +1,33 @@
 use eframe::egui;
 use crate::logger;
 use crate::delete;
+use crate::process_check;
+use crate::protected;
 use crate::utils;
 
-pub fn show_confirmation(ctx: &egui::Context, message: &str, status: &Option<String>) -> Option<bool> {
+/// 待确认的删除请求
+#[derive(Debug, Clone)]
+pub struct DeleteRequest {
+    pub folder_name: String,
+    pub options: delete::DeleteOptions,
+}
+
+impl DeleteRequest {
+    pub fn new(folder_name: String) -> Self {
+        Self {
+            folder_name,
+            options: delete::DeleteOptions::default(),
+        }
+    }
+}
+
+/// 确认窗口。传入 `secure_delete` 时显示"安全删除"选项
+pub fn show_confirmation(
+    ctx: &egui::Context,
+    message: &str,
+    status: &Option<String>,
+    secure_delete: Option<&mut bool>,
+) -> Option<bool> {
     let mut result = None;
 
     egui::Window::new("确认操作")
@@ -12,6 +36,11 @@ pub fn show_confirmation(ctx: &egui::Context, message: &str, status: &Option<Str
         .show(ctx, |ui| {
             ui.label(message);
 
+            if let (Some(secure), None) = (secure_delete, status) {
+                ui.checkbox(secure, "安全删除（先覆盖文件内容，速度较慢）")
+                    .on_hover_text("用于浏览器配置、聊天记录、登录凭据等敏感数据");
+            }
+
             // 显示状态信息
             if let Some(status_message) = status {
                 ui.label(status_message);
@@ -39,47 +68,261 @@ pub fn show_confirmation(ctx: &egui::Context, message: &str, status: &Option<Str
 
 pub fn handle_delete_confirmation(
     ctx: &egui::Context,
-    confirm_delete: &mut Option<(String, bool)>,
+    confirm_delete: &mut Option<DeleteRequest>,
+    delete_task: &mut Option<delete::DeleteTask>,
+    process_scan: &mut Option<process_check::ProcessCheck>,
     selected_appdata_folder: &str,
     status: &mut Option<String>,
     folder_data: &mut Vec<(String, u64)>, // 新增参数
 ) {
-    if let Some((folder_name, _)) = confirm_delete.clone() {
-        let message = format!("确定要彻底删除文件夹 {} 吗？", folder_name);
-        logger::log_info(&message);
-        if let Some(confirm) = show_confirmation(ctx, &message, status) {
-            if confirm {
-                if let Some(base_path) = utils::get_appdata_dir(selected_appdata_folder) {
-                    let full_path = base_path.join(&folder_name);
-                    match delete::delete_folder(&full_path) {
-                        Ok(_) => {
-                            // 检查文件夹是否已成功删除
-                            if !full_path.exists() {
-                                *status = Some(format!("文件夹 {} 已成功删除", folder_name));
-                                println!("文件夹 {} 已成功删除", folder_name);
-                                // 从 folder_data 中移除对应项目
-                                folder_data.retain(|(name, _)| name != &folder_name);
-                            } else {
-                                *status = Some(format!("文件夹 {} 删除失败", folder_name));
-                            }
-                        }
-                        Err(err) => {
-                            eprintln!("Error: {}", err);
-                            logger::log_error(&format!("Error: {}", err));
-                            *status = Some(format!("删除文件夹 {} 时发生错误: {}", folder_name, err));
-                        }
-                    }
-                } else {
-                    eprintln!("无法获取 {} 文件夹路径", selected_appdata_folder);
-                    logger::log_error(&format!(
-                        "无法获取 {} 文件夹路径",
-                        selected_appdata_folder
-                    ));
-                    *status = Some(format!("无法获取 {} 文件夹路径", selected_appdata_folder));
+    // 删除正在后台进行时，显示进度窗口
+    if let Some(task) = delete_task {
+        if task.report.is_none() {
+            task.poll();
+            match task.report.clone() {
+                Some(report) => *status = Some(finish_delete(task, &report, folder_data)),
+                None => {
+                    show_delete_progress(ctx, task);
+                    ctx.request_repaint();
+                    return;
+                }
+            }
+        }
+        show_delete_failures(ctx, task, status, folder_data);
+    }
+
+    // 在后台检查占用情况，有进程正在使用文件夹时，先让用户决定是否继续
+    if let Some(scan) = process_scan {
+        if let Some(proceed) = scan.show(ctx) {
+            *process_scan = None;
+            match (proceed, confirm_delete.clone()) {
+                (true, Some(request)) => {
+                    start_delete(delete_task, &request, selected_appdata_folder, status)
+                }
+                _ => *confirm_delete = None,
+            }
+        }
+        return;
+    }
+
+    let Some(request) = confirm_delete.as_mut() else {
+        return;
+    };
+    let folder_name = request.folder_name.clone();
+
+    // 受保护的文件夹：禁止删除，或先输入名称确认
+    if status.is_none() && !request.options.allow_protected {
+        match protected::check(selected_appdata_folder, &folder_name) {
+            Some(entry) if entry.severity == protected::Severity::Blocked => {
+                logger::log_info(&format!("拒绝删除受保护的文件夹: {}", folder_name));
+                *status = Some(format!(
+                    "文件夹 {} 受保护（{}），禁止删除",
+                    folder_name, entry.reason
+                ));
+            }
+            Some(entry) => {
+                match protected::show_typed_confirmation(ctx, "删除", &entry) {
+                    Some(true) => request.options.allow_protected = true,
+                    Some(false) => *confirm_delete = None,
+                    None => {}
                 }
-            } else {
-                *confirm_delete = None; // 用户选择关闭或取消
+                return;
+            }
+            None => {}
+        }
+    }
+
+    let message = format!("确定要彻底删除文件夹 {} 吗？", folder_name);
+    logger::log_info(&message);
+    if let Some(confirm) =
+        show_confirmation(ctx, &message, status, Some(&mut request.options.secure))
+    {
+        if confirm {
+            let request = request.clone();
+            check_processes(process_scan, &request, selected_appdata_folder, status);
+        } else {
+            *confirm_delete = None; // 用户选择关闭或取消
+            *delete_task = None;
+        }
+    }
+}
+
+// 开始删除前先在后台检查是否有进程在使用文件夹，结果在下一帧处理
+fn check_processes(
+    process_scan: &mut Option<process_check::ProcessCheck>,
+    request: &DeleteRequest,
+    selected_appdata_folder: &str,
+    status: &mut Option<String>,
+) {
+    match utils::get_appdata_dir(selected_appdata_folder) {
+        Some(base_path) => {
+            *process_scan = Some(process_check::ProcessCheck::start(
+                base_path.join(&request.folder_name),
+                "删除",
+            ));
+        }
+        None => report_missing_root(selected_appdata_folder, status),
+    }
+}
+
+fn start_delete(
+    delete_task: &mut Option<delete::DeleteTask>,
+    request: &DeleteRequest,
+    selected_appdata_folder: &str,
+    status: &mut Option<String>,
+) {
+    match utils::get_appdata_dir(selected_appdata_folder) {
+        Some(base_path) => {
+            let full_path = base_path.join(&request.folder_name);
+            *delete_task = Some(delete::DeleteTask::start(
+                request.folder_name.clone(),
+                full_path,
+                request.options,
+            ));
+        }
+        None => report_missing_root(selected_appdata_folder, status),
+    }
+}
+
+fn report_missing_root(selected_appdata_folder: &str, status: &mut Option<String>) {
+    eprintln!("无法获取 {} 文件夹路径", selected_appdata_folder);
+    logger::log_error(&format!("无法获取 {} 文件夹路径", selected_appdata_folder));
+    *status = Some(format!("无法获取 {} 文件夹路径", selected_appdata_folder));
+}
+
+fn show_delete_progress(ctx: &egui::Context, task: &delete::DeleteTask) {
+    egui::Window::new("正在删除")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(format!("正在删除文件夹: {}", task.folder_name));
+            ui.label(format!("已删除文件: {}", task.progress.files_removed));
+            ui.label(format!(
+                "已释放空间: {}",
+                utils::format_size(task.progress.bytes_freed)
+            ));
+            ui.label(format!("当前: {}", task.progress.current_path.display()));
+
+            if task.is_cancelling() {
+                ui.label("正在取消...");
+            } else if ui.button("取消").clicked() {
+                task.cancel();
+                println!("用户取消删除");
+            }
+        });
+}
+
+// 删除结束后同步更新列表，并生成状态信息
+fn finish_delete(
+    task: &delete::DeleteTask,
+    report: &delete::DeleteReport,
+    folder_data: &mut Vec<(String, u64)>,
+) -> String {
+    let folder_name = &task.folder_name;
+
+    if !task.folder_path.exists() {
+        println!("文件夹 {} 已成功删除", folder_name);
+        // 从 folder_data 中移除对应项目
+        folder_data.retain(|(name, _)| name != folder_name);
+    } else if let Some((_, size)) = folder_data.iter_mut().find(|(name, _)| name == folder_name) {
+        // 部分删除：更新列表中剩余的大小
+        *size = size.saturating_sub(report.bytes_freed);
+    }
+
+    delete_status_message(task, report)
+}
+
+fn delete_status_message(task: &delete::DeleteTask, report: &delete::DeleteReport) -> String {
+    let folder_name = &task.folder_name;
+
+    let mut message = if let Some(err) = &report.error {
+        eprintln!("Error: {}", err);
+        format!("删除文件夹 {} 时发生错误: {}", folder_name, err)
+    } else if report.cancelled {
+        format!(
+            "已取消删除 {}：{}，剩余内容仍保留在原文件夹中",
+            folder_name,
+            report.summary()
+        )
+    } else if report.is_complete() && !task.folder_path.exists() {
+        format!("文件夹 {} 已成功删除（{}）", folder_name, report.summary())
+    } else {
+        format!("文件夹 {} 未能完全删除：{}", folder_name, report.summary())
+    };
+
+    // 安全删除无法保证的情况需要告知用户
+    if !report.caveats.is_empty() {
+        message.push_str("\n安全删除无法保证：");
+        for caveat in &report.caveats {
+            message.push_str(&format!("\n- {}", caveat));
+        }
+    }
+    message
+}
+
+// 列出删除失败的条目，每一项都可以单独重试
+fn show_delete_failures(
+    ctx: &egui::Context,
+    task: &mut delete::DeleteTask,
+    status: &mut Option<String>,
+    folder_data: &mut Vec<(String, u64)>,
+) {
+    // 后台重试结束后同步更新列表和状态信息
+    if task.retrying.is_some() {
+        let freed_before = task.report.as_ref().map_or(0, |r| r.bytes_freed);
+        task.poll();
+        if task.retrying.is_some() {
+            ctx.request_repaint();
+        } else if let Some(report) = task.report.clone() {
+            let bytes_freed = report.bytes_freed.saturating_sub(freed_before);
+            if !task.folder_path.exists() {
+                folder_data.retain(|(name, _)| name != &task.folder_name);
+            } else if let Some((_, size)) = folder_data
+                .iter_mut()
+                .find(|(name, _)| name == &task.folder_name)
+            {
+                *size = size.saturating_sub(bytes_freed);
             }
+            *status = Some(delete_status_message(task, &report));
         }
     }
-}
\ No newline at end of file
+
+    let Some(report) = task.report.as_ref() else {
+        return;
+    };
+    if report.failures.is_empty() {
+        return;
+    }
+
+    let mut retry_index = None;
+    egui::Window::new("删除失败的项目")
+        .collapsible(false)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("delete_failures")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for (index, failure) in report.failures.iter().enumerate() {
+                                ui.label(failure.path.display().to_string());
+                                ui.label(&failure.reason).on_hover_text(&failure.error);
+                                if task.retrying.as_ref() == Some(&failure.path) {
+                                    ui.label("正在重试...");
+                                } else if ui
+                                    .add_enabled(task.retrying.is_none(), egui::Button::new("重试"))
+                                    .clicked()
+                                {
+                                    retry_index = Some(index);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+        });
+
+    if let Some(index) = retry_index {
+        task.retry(index);
+        ctx.request_repaint();
+    }
+}
@@ -0,0 +1,458 @@
+use crate::app_dirs;
+use crate::delete;
+use crate::logger;
+use crate::protected;
+use crate::scanner;
+use crate::utils;
+use eframe::egui;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use walkdir::WalkDir;
+
+pub const CLEAN_RULES_FILE: &str = "clean_rules.yaml";
+
+// 通用规则：任意层级下名称匹配的子文件夹都视为可清理的缓存或日志
+const GENERIC_PATTERNS: [&str; 5] = ["Cache", "Code Cache", "GPUCache", "logs", "Crashpad"];
+
+/// 清理规则。`generic` 为通用子文件夹名，其余按根目录和程序文件夹名列出相对路径
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct CleanRules {
+    generic: Vec<String>,
+    #[serde(rename = "Roaming")]
+    roaming: HashMap<String, Vec<String>>,
+    #[serde(rename = "Local")]
+    local: HashMap<String, Vec<String>>,
+    #[serde(rename = "LocalLow")]
+    local_low: HashMap<String, Vec<String>>,
+}
+
+impl CleanRules {
+    fn load() -> Self {
+        let Ok(content) = fs::read_to_string(app_dirs::config_file(CLEAN_RULES_FILE)) else {
+            return Self::default();
+        };
+        serde_yaml::from_str(&content).unwrap_or_else(|e| {
+            logger::log_error(&format!("解析清理规则失败: {}", e));
+            Self::default()
+        })
+    }
+
+    fn app_rules(&self, root: &str, folder_name: &str) -> &[String] {
+        let rules = match root {
+            "Roaming" => &self.roaming,
+            "Local" => &self.local,
+            "LocalLow" => &self.local_low,
+            _ => return &[],
+        };
+        rules.get(folder_name).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+/// 一条规则匹配到的所有子路径
+#[derive(Debug, Clone)]
+pub struct CleanGroup {
+    pub pattern: String,
+    pub paths: Vec<PathBuf>,
+    pub size: u64,
+    pub selected: bool,
+}
+
+/// 单条规则的清理结果
+#[derive(Debug, Clone)]
+pub struct CleanResult {
+    pub pattern: String,
+    pub bytes_freed: u64,
+    pub failures: usize,
+}
+
+enum CleanMessage {
+    Scanned(Vec<CleanGroup>),
+    PatternCleaned(CleanResult),
+    Finished { cancelled: bool },
+}
+
+/// 查找文件夹中符合清理规则的子路径
+pub fn find_clean_groups(root: &str, folder_name: &str, folder_path: &Path) -> Vec<CleanGroup> {
+    find_groups_with(&CleanRules::load(), root, folder_name, folder_path)
+}
+
+// 规则中的子路径只能由普通的文件夹名组成，不能是绝对路径或包含 ".."
+fn is_plain_subpath(subpath: &str) -> bool {
+    let mut components = Path::new(subpath).components().peekable();
+    components.peek().is_some() && components.all(|c| matches!(c, Component::Normal(_)))
+}
+
+// 匹配到的路径必须是真实的文件夹（不是符号链接），并且允许删除。
+// 中间的文件夹也可能是指向别处的链接，所以还要确认实际位置仍在程序文件夹内
+fn is_cleanable(path: &Path, folder_path: &Path) -> bool {
+    if !fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir()) {
+        return false;
+    }
+    let inside = match (fs::canonicalize(path), fs::canonicalize(folder_path)) {
+        (Ok(resolved), Ok(folder)) => resolved.starts_with(folder),
+        _ => false,
+    };
+    if !inside {
+        logger::log_info(&format!(
+            "{} 实际位于文件夹 {} 之外，不清理",
+            path.display(),
+            folder_path.display()
+        ));
+        return false;
+    }
+    match protected::ensure_allowed(path, "清理", false) {
+        Ok(()) => true,
+        Err(err) => {
+            logger::log_info(&err);
+            false
+        }
+    }
+}
+
+fn find_groups_with(
+    rules: &CleanRules,
+    root: &str,
+    folder_name: &str,
+    folder_path: &Path,
+) -> Vec<CleanGroup> {
+    let mut groups: Vec<CleanGroup> = Vec::new();
+
+    // 程序专用规则：相对路径
+    for subpath in rules.app_rules(root, folder_name) {
+        if !is_plain_subpath(subpath) {
+            logger::log_error(&format!("忽略无效的清理规则: {}/{}", folder_name, subpath));
+            continue;
+        }
+        let path = folder_path.join(subpath);
+        if is_cleanable(&path, folder_path) {
+            groups.push(CleanGroup {
+                pattern: subpath.clone(),
+                size: scanner::calculate_folder_size(&path),
+                paths: vec![path],
+                selected: true,
+            });
+        }
+    }
+
+    // 通用规则：按子文件夹名匹配，匹配后不再深入
+    let generic: Vec<String> = GENERIC_PATTERNS
+        .iter()
+        .map(|p| p.to_string())
+        .chain(rules.generic.iter().cloned())
+        .collect();
+    let mut walker = WalkDir::new(folder_path).min_depth(1).into_iter();
+    while let Some(entry) = walker.next() {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy();
+        let Some(pattern) = generic.iter().find(|p| p.eq_ignore_ascii_case(&name)) else {
+            continue;
+        };
+        walker.skip_current_dir();
+        if !is_cleanable(entry.path(), folder_path) {
+            continue;
+        }
+
+        // 已被程序专用规则覆盖的路径不再重复统计
+        if groups
+            .iter()
+            .any(|g| g.paths.iter().any(|p| entry.path().starts_with(p)))
+        {
+            continue;
+        }
+
+        let size = scanner::calculate_folder_size(entry.path());
+        match groups.iter_mut().find(|g| &g.pattern == pattern) {
+            Some(group) => {
+                group.paths.push(entry.path().to_path_buf());
+                group.size += size;
+            }
+            None => groups.push(CleanGroup {
+                pattern: pattern.clone(),
+                paths: vec![entry.path().to_path_buf()],
+                size,
+                selected: true,
+            }),
+        }
+    }
+
+    groups.sort_by_key(|g| std::cmp::Reverse(g.size));
+    groups
+}
+
+// 逐条规则删除匹配的子路径，删除本身交给 delete 模块
+fn clean_groups(groups: Vec<CleanGroup>, cancel_flag: &AtomicBool, tx: &Sender<CleanMessage>) {
+    for group in groups.into_iter().filter(|g| g.selected) {
+        let mut result = CleanResult {
+            pattern: group.pattern,
+            bytes_freed: 0,
+            failures: 0,
+        };
+
+        for path in &group.paths {
+            match delete::delete_folder(path, delete::DeleteOptions::default(), cancel_flag, |_| {})
+            {
+                Ok(report) => {
+                    result.bytes_freed += report.bytes_freed;
+                    result.failures += report.failures.len();
+                }
+                Err(err) => {
+                    logger::log_error(&err);
+                    result.failures += 1;
+                }
+            }
+        }
+
+        logger::log_info(&format!(
+            "清理 {}: 释放 {}",
+            result.pattern,
+            utils::format_size(result.bytes_freed)
+        ));
+        let _ = tx.send(CleanMessage::PatternCleaned(result));
+
+        if cancel_flag.load(Ordering::Relaxed) {
+            let _ = tx.send(CleanMessage::Finished { cancelled: true });
+            return;
+        }
+    }
+    let _ = tx.send(CleanMessage::Finished { cancelled: false });
+}
+
+/// "清理内容"窗口：只删除文件夹中的缓存、日志等子路径，保留程序设置
+#[derive(Default)]
+pub struct ContentCleaner {
+    pub show_window: bool,
+    folder_name: String,
+    groups: Vec<CleanGroup>,
+    results: Vec<CleanResult>,
+    status_message: Option<String>,
+    is_busy: bool,
+    rx: Option<Receiver<CleanMessage>>,
+    cancel_flag: Arc<AtomicBool>,
+    freed: Option<(String, u64)>, // 清理完成后待同步到列表的释放空间
+}
+
+impl ContentCleaner {
+    /// 打开窗口并在后台扫描匹配的子路径
+    pub fn open(&mut self, root: &str, folder_name: &str) {
+        let Some(base_path) = utils::get_appdata_dir(root) else {
+            return;
+        };
+
+        self.show_window = true;
+        self.folder_name = folder_name.to_string();
+        self.groups.clear();
+        self.results.clear();
+        self.status_message = Some("正在查找可清理的内容...".to_string());
+        self.is_busy = true;
+
+        let (tx, rx) = mpsc::channel();
+        self.rx = Some(rx);
+        let root = root.to_string();
+        let folder_name = folder_name.to_string();
+        thread::spawn(move || {
+            let groups = find_clean_groups(&root, &folder_name, &base_path.join(&folder_name));
+            let _ = tx.send(CleanMessage::Scanned(groups));
+        });
+    }
+
+    /// 取出清理释放的空间，用于更新主界面的文件夹大小
+    pub fn take_freed(&mut self) -> Option<(String, u64)> {
+        self.freed.take()
+    }
+
+    fn start_clean(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        self.rx = Some(rx);
+        self.results.clear();
+        self.is_busy = true;
+        self.status_message = Some("正在清理...".to_string());
+        self.cancel_flag = Arc::new(AtomicBool::new(false));
+
+        let groups = self.groups.clone();
+        let cancel_flag = Arc::clone(&self.cancel_flag);
+        thread::spawn(move || clean_groups(groups, &cancel_flag, &tx));
+    }
+
+    fn poll(&mut self) {
+        let Some(rx) = &self.rx else {
+            return;
+        };
+        while let Ok(msg) = rx.try_recv() {
+            match msg {
+                CleanMessage::Scanned(groups) => {
+                    self.status_message = if groups.is_empty() {
+                        Some("没有找到可清理的内容".to_string())
+                    } else {
+                        None
+                    };
+                    self.groups = groups;
+                    self.is_busy = false;
+                }
+                CleanMessage::PatternCleaned(result) => self.results.push(result),
+                CleanMessage::Finished { cancelled } => {
+                    let freed: u64 = self.results.iter().map(|r| r.bytes_freed).sum();
+                    let failures: usize = self.results.iter().map(|r| r.failures).sum();
+                    let mut message = format!("清理完成，共释放 {}", utils::format_size(freed));
+                    if failures > 0 {
+                        message.push_str(&format!("，{} 个条目删除失败", failures));
+                    }
+                    if cancelled {
+                        message = format!("已取消清理，{}", message);
+                    }
+                    self.status_message = Some(message);
+                    self.freed = Some((self.folder_name.clone(), freed));
+                    self.groups.clear();
+                    self.is_busy = false;
+                }
+            }
+        }
+    }
+
+    pub fn show_clean_window(&mut self, ctx: &egui::Context) {
+        if !self.show_window {
+            return;
+        }
+        self.poll();
+        if self.is_busy {
+            ctx.request_repaint();
+        }
+
+        let mut open = true;
+        egui::Window::new("清理内容")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(format!("文件夹: {}", self.folder_name));
+                ui.label("只删除下列缓存和日志，保留程序设置");
+
+                if !self.groups.is_empty() {
+                    egui::Grid::new("clean_groups")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for group in &mut self.groups {
+                                ui.add_enabled(
+                                    !self.is_busy,
+                                    egui::Checkbox::new(&mut group.selected, &group.pattern),
+                                );
+                                ui.label(format!("{} 处", group.paths.len()));
+                                ui.label(utils::format_size(group.size));
+                                ui.end_row();
+                            }
+                        });
+
+                    let selected: u64 = self
+                        .groups
+                        .iter()
+                        .filter(|g| g.selected)
+                        .map(|g| g.size)
+                        .sum();
+                    ui.label(format!("预计释放: {}", utils::format_size(selected)));
+                }
+
+                if !self.results.is_empty() {
+                    ui.separator();
+                    egui::Grid::new("clean_results")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for result in &self.results {
+                                ui.label(&result.pattern);
+                                ui.label(format!(
+                                    "已释放 {}",
+                                    utils::format_size(result.bytes_freed)
+                                ));
+                                if result.failures > 0 {
+                                    ui.label(format!("{} 个失败", result.failures));
+                                }
+                                ui.end_row();
+                            }
+                        });
+                }
+
+                if let Some(message) = &self.status_message {
+                    ui.label(message);
+                }
+
+                ui.horizontal(|ui| {
+                    if self.is_busy {
+                        if !self.groups.is_empty() && ui.button("取消").clicked() {
+                            self.cancel_flag.store(true, Ordering::Relaxed);
+                        }
+                    } else if !self.groups.is_empty() && ui.button("清理所选").clicked() {
+                        self.start_clean();
+                    }
+                });
+            });
+
+        if !open {
+            self.cancel_flag.store(true, Ordering::Relaxed);
+            self.show_window = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_app_and_generic_groups_and_rejects_escaping_subpaths() {
+        let dir = std::env::temp_dir().join(format!("adc_content_cleaner_{}", std::process::id()));
+        let folder = dir.join("App");
+        for sub in ["data/tmp", "profile/Cache", "data/tmp/Cache", "outside"] {
+            fs::create_dir_all(folder.join(sub)).unwrap();
+        }
+        fs::create_dir_all(dir.join("outside")).unwrap();
+        fs::write(folder.join("profile/Cache/a.bin"), [0u8; 16]).unwrap();
+        fs::write(folder.join("data/tmp/b.bin"), [0u8; 8]).unwrap();
+        // 指向文件夹外的符号链接不能被当作可清理的路径
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.join("outside"), folder.join("link")).unwrap();
+        // 中间的文件夹是链接时，最后一级虽然是真实文件夹，实际位置也在文件夹外
+        fs::create_dir_all(dir.join("elsewhere/tmp")).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.join("elsewhere"), folder.join("linked")).unwrap();
+
+        let mut rules = CleanRules::default();
+        rules.roaming.insert(
+            "App".to_string(),
+            [
+                "data/tmp",
+                "../outside",
+                "/etc",
+                "",
+                "missing",
+                "link",
+                "linked/tmp",
+            ]
+            .map(String::from)
+            .to_vec(),
+        );
+
+        let groups = find_groups_with(&rules, "Roaming", "App", &folder);
+        let patterns: Vec<&str> = groups.iter().map(|g| g.pattern.as_str()).collect();
+        assert_eq!(patterns, ["Cache", "data/tmp"]);
+
+        // data/tmp/Cache 已被程序专用规则覆盖，只统计 profile/Cache
+        let cache = &groups[0];
+        assert_eq!(cache.paths, [folder.join("profile/Cache")]);
+        assert_eq!(cache.size, 16);
+        assert_eq!(groups[1].paths, [folder.join("data/tmp")]);
+
+        assert!(!is_plain_subpath("a/../b"));
+        assert!(is_plain_subpath("a/b"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,13 +1,380 @@
-use std::fs;
-use std::path::PathBuf;
 use crate::logger;
+use crate::protected;
+use crate::secure_delete;
+use crate::utils;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use walkdir::WalkDir;
+
+/// 删除选项
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeleteOptions {
+    pub allow_protected: bool, // 用户已通过输入名称确认删除受保护的文件夹
+    pub secure: bool,          // 删除前覆盖文件内容（安全删除）
+}
+
+/// 删除进度，由后台线程定期发送给界面
+#[derive(Debug, Clone, Default)]
+pub struct DeleteProgress {
+    pub files_removed: u64,    // 已删除的文件数
+    pub bytes_freed: u64,      // 已释放的空间
+    pub current_path: PathBuf, // 当前正在删除的路径
+}
+
+/// 删除失败的单个条目
+#[derive(Debug, Clone)]
+pub struct DeleteFailure {
+    pub path: PathBuf,
+    pub error: String,  // 系统返回的错误信息
+    pub reason: String, // 便于用户理解的原因
+}
+
+impl DeleteFailure {
+    fn new(path: &Path, err: &io::Error) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            error: err.to_string(),
+            reason: describe_io_error(err).to_string(),
+        }
+    }
+}
+
+/// 删除结束后的结果报告
+#[derive(Debug, Clone, Default)]
+pub struct DeleteReport {
+    pub files_removed: u64,
+    pub bytes_freed: u64,
+    pub cancelled: bool,              // 是否被用户取消
+    pub error: Option<String>,        // 导致无法开始删除的错误
+    pub failures: Vec<DeleteFailure>, // 删除失败的条目，其余内容会继续删除
+    pub secure: bool,                 // 是否使用了安全删除
+    pub caveats: Vec<String>,         // 安全删除无法保证彻底清除的情况
+    pub skipped: u64,                 // 删除前复查时不再符合条件而跳过的文件数
+}
+
+impl DeleteReport {
+    /// 删除是否完整完成
+    pub fn is_complete(&self) -> bool {
+        !self.cancelled && self.error.is_none() && self.failures.is_empty()
+    }
+
+    /// 生成删除结果摘要，例如 "已删除 1,204 个文件，3 个失败: ..."
+    pub fn summary(&self) -> String {
+        const MAX_LISTED: usize = 5;
+
+        let mut summary = format!(
+            "已删除 {} 个文件，释放 {}",
+            utils::format_count(self.files_removed),
+            utils::format_size(self.bytes_freed)
+        );
+        if self.secure {
+            summary.push_str("，文件内容已覆盖");
+        }
+        if self.skipped > 0 {
+            summary.push_str(&format!(
+                "，跳过 {} 个已变化的文件",
+                utils::format_count(self.skipped)
+            ));
+        }
+
+        if !self.failures.is_empty() {
+            let listed: Vec<String> = self
+                .failures
+                .iter()
+                .take(MAX_LISTED)
+                .map(|f| format!("{}（{}）", f.path.display(), f.reason))
+                .collect();
+            summary.push_str(&format!(
+                "，{} 个失败: {}",
+                utils::format_count(self.failures.len() as u64),
+                listed.join("、")
+            ));
+            if self.failures.len() > MAX_LISTED {
+                summary.push_str(" …");
+            }
+        }
+
+        summary
+    }
+}
+
+impl DeleteReport {
+    // 用重试结果替换原来的失败条目，目录重试可能留下多个新的失败条目
+    fn apply_retry(&mut self, path: &Path, retried: DeleteReport) {
+        self.files_removed += retried.files_removed;
+        self.bytes_freed += retried.bytes_freed;
+        match self.failures.iter().position(|f| f.path == path) {
+            Some(index) => {
+                self.failures.splice(index..=index, retried.failures);
+            }
+            None => self.failures.extend(retried.failures),
+        }
+    }
+}
+
+pub enum DeleteMessage {
+    Progress(DeleteProgress),
+    Finished(DeleteReport),
+    Retried(PathBuf, DeleteReport), // 重试某个失败条目的结果
+}
+
+/// 在后台线程中运行的删除任务，界面通过 `poll` 获取进度
+pub struct DeleteTask {
+    pub folder_name: String,
+    pub folder_path: PathBuf,
+    pub progress: DeleteProgress,
+    pub report: Option<DeleteReport>,
+    pub retrying: Option<PathBuf>, // 正在后台重试的失败条目
+    tx: Sender<DeleteMessage>,
+    rx: Receiver<DeleteMessage>,
+    cancel_flag: Arc<AtomicBool>,
+}
+
+impl DeleteTask {
+    pub fn start(folder_name: String, folder_path: PathBuf, options: DeleteOptions) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let cancel_flag = Arc::new(AtomicBool::new(false));
+
+        let worker_path = folder_path.clone();
+        let worker_cancel = Arc::clone(&cancel_flag);
+        let worker_tx = tx.clone();
+        thread::spawn(move || {
+            let report = delete_folder(&worker_path, options, &worker_cancel, |progress| {
+                let _ = worker_tx.send(DeleteMessage::Progress(progress.clone()));
+            })
+            .unwrap_or_else(|err| DeleteReport {
+                error: Some(err),
+                ..Default::default()
+            });
+            let _ = worker_tx.send(DeleteMessage::Finished(report));
+        });
+
+        Self {
+            folder_name,
+            folder_path,
+            progress: DeleteProgress::default(),
+            report: None,
+            retrying: None,
+            tx,
+            rx,
+            cancel_flag,
+        }
+    }
+
+    /// 在后台线程中重试删除第 `index` 个失败条目，同一时间只重试一个
+    pub fn retry(&mut self, index: usize) {
+        if self.retrying.is_some() {
+            return;
+        }
+        let Some(report) = &self.report else {
+            return;
+        };
+        let Some(failure) = report.failures.get(index).cloned() else {
+            return;
+        };
+
+        let secure = report.secure;
+        let root = self.folder_path.clone();
+        let tx = self.tx.clone();
+        self.retrying = Some(failure.path.clone());
+        thread::spawn(move || {
+            let result = retry_failure(&failure, &root, secure);
+            let _ = tx.send(DeleteMessage::Retried(failure.path, result));
+        });
+    }
+
+    /// 读取后台线程发来的所有消息，不会阻塞界面
+    pub fn poll(&mut self) {
+        while let Ok(msg) = self.rx.try_recv() {
+            match msg {
+                DeleteMessage::Progress(progress) => self.progress = progress,
+                DeleteMessage::Finished(report) => self.report = Some(report),
+                DeleteMessage::Retried(path, result) => {
+                    self.retrying = None;
+                    if let Some(report) = &mut self.report {
+                        report.apply_retry(&path, result);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 请求取消，后台线程会在删除下一个文件前停止
+    pub fn cancel(&self) {
+        self.cancel_flag.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelling(&self) -> bool {
+        self.cancel_flag.load(Ordering::Relaxed) && self.report.is_none()
+    }
+}
+
+// 逐个删除文件夹中的内容，每删除一个文件回调一次进度，
+// 每个文件之间检查取消标志
+fn remove_dir_with_progress(
+    folder_path: &Path,
+    secure: bool,
+    cancel_flag: &AtomicBool,
+    mut on_progress: impl FnMut(&DeleteProgress),
+) -> DeleteReport {
+    let mut progress = DeleteProgress::default();
+    let mut report = DeleteReport::default();
+    let mut not_wiped = 0;
+
+    // contents_first 保证先删除文件，再删除它所在的目录
+    for entry in WalkDir::new(folder_path).contents_first(true) {
+        if cancel_flag.load(Ordering::Relaxed) {
+            report.cancelled = true;
+            break;
+        }
+
+        // 单个条目失败时记录下来，继续删除其余内容
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                let path = err.path().unwrap_or(folder_path).to_path_buf();
+                let err: io::Error = err.into();
+                logger::log_error(&format!("无法读取条目: {} - 错误: {}", path.display(), err));
+                report.failures.push(DeleteFailure::new(&path, &err));
+                continue;
+            }
+        };
+
+        let path = entry.path();
+        progress.current_path = path.to_path_buf();
 
-/// 删除文件夹，接受 `PathBuf` 类型
-pub fn delete_folder(folder_path: &PathBuf) -> Result<(), String> {
+        let result = if entry.file_type().is_dir() {
+            // 子项删除失败时目录必然非空，不再重复报告
+            if report.failures.iter().any(|f| f.path.starts_with(path)) {
+                continue;
+            }
+            fs::remove_dir(path)
+        } else {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            remove_file(path, secure).map(|wiped| {
+                progress.files_removed += 1;
+                progress.bytes_freed += size;
+                if secure && !wiped {
+                    not_wiped += 1;
+                }
+            })
+        };
+
+        if let Err(err) = result {
+            logger::log_error(&format!("删除失败: {} - 错误: {}", path.display(), err));
+            report.failures.push(DeleteFailure::new(path, &err));
+        }
+
+        on_progress(&progress);
+    }
+
+    report.files_removed = progress.files_removed;
+    report.bytes_freed = progress.bytes_freed;
+    report.caveats.extend(not_wiped_caveat(not_wiped));
+
+    if report.cancelled {
+        logger::log_info(&format!(
+            "删除已取消: {}，已删除 {} 个文件",
+            folder_path.display(),
+            report.files_removed
+        ));
+    } else if !report.failures.is_empty() {
+        logger::log_error(&format!(
+            "文件夹部分删除: {}，{} 个条目删除失败",
+            folder_path.display(),
+            report.failures.len()
+        ));
+    } else {
+        logger::log_info(&format!("文件夹已删除: {}", folder_path.display()));
+    }
+
+    report
+}
+
+/// 删除一组文件，单个文件失败时记录下来并继续。
+/// 每个文件删除前先调用 `still_wanted` 复查，返回 false 时跳过该文件。
+/// 与 `delete_folder` 使用相同的进度回调、取消标志和失败报告
+pub fn delete_files(
+    files: &[PathBuf],
+    secure: bool,
+    cancel_flag: &AtomicBool,
+    still_wanted: impl Fn(&Path) -> bool,
+    mut on_progress: impl FnMut(&DeleteProgress),
+) -> DeleteReport {
+    let mut progress = DeleteProgress::default();
+    let mut report = DeleteReport::default();
+    let mut not_wiped = 0;
+    logger::log_info(&format!("尝试删除 {} 个文件", files.len()));
+
+    for path in files {
+        if cancel_flag.load(Ordering::Relaxed) {
+            report.cancelled = true;
+            break;
+        }
+
+        if !still_wanted(path) {
+            logger::log_info(&format!("跳过已变化的文件: {}", path.display()));
+            report.skipped += 1;
+            continue;
+        }
+
+        progress.current_path = path.clone();
+        let size = fs::symlink_metadata(path).map(|m| m.len()).unwrap_or(0);
+        match remove_file(path, secure) {
+            Ok(wiped) => {
+                progress.files_removed += 1;
+                progress.bytes_freed += size;
+                if secure && !wiped {
+                    not_wiped += 1;
+                }
+            }
+            Err(err) => {
+                logger::log_error(&format!("删除失败: {} - 错误: {}", path.display(), err));
+                report.failures.push(DeleteFailure::new(path, &err));
+            }
+        }
+
+        on_progress(&progress);
+    }
+
+    report.files_removed = progress.files_removed;
+    report.bytes_freed = progress.bytes_freed;
+    report.secure = secure;
+    report.caveats.extend(not_wiped_caveat(not_wiped));
+    logger::log_audit(&format!(
+        "删除 {} 个文件（{}）: {}",
+        files.len(),
+        delete_mode(secure),
+        report.summary()
+    ));
+    report
+}
+
+/// 删除文件夹。
+/// 受保护的文件夹会被拒绝，除非 `options.allow_protected` 为 true 且保护级别允许确认后删除。
+/// 删除过程中通过 `on_progress` 回调报告进度，`cancel_flag` 置位后在下一个文件前停止
+pub fn delete_folder(
+    folder_path: &Path,
+    options: DeleteOptions,
+    cancel_flag: &AtomicBool,
+    on_progress: impl FnMut(&DeleteProgress),
+) -> Result<DeleteReport, String> {
     let folder_path_str = folder_path.to_string_lossy();
     println!("尝试删除文件夹: {}", folder_path_str);
     logger::log_info(&format!("尝试删除文件夹: {}", folder_path_str));
 
+    if let Err(error_msg) = protected::ensure_allowed(folder_path, "删除", options.allow_protected)
+    {
+        println!("{}", error_msg);
+        logger::log_error(&error_msg);
+        return Err(error_msg);
+    }
+
     if !folder_path.exists() {
         let error_msg = format!("文件夹不存在: {}", folder_path_str);
         println!("{}", error_msg);
@@ -16,12 +383,27 @@ pub fn delete_folder(folder_path: &PathBuf) -> Result<(), String> {
     }
 
     if folder_path.is_dir() {
-        fs::remove_dir_all(folder_path).map_err(|e| {
-            let error_msg = format!("删除失败: {} - 错误: {}", folder_path_str, e);
-            println!("{}", error_msg);
-            logger::log_error(&error_msg);
-            error_msg
-        })
+        // 删除前检查存储设备，删除后路径已不存在
+        let caveats = if options.secure {
+            secure_delete::storage_caveats(folder_path)
+        } else {
+            Vec::new()
+        };
+        let mut report =
+            remove_dir_with_progress(folder_path, options.secure, cancel_flag, on_progress);
+        report.secure = options.secure;
+        report.caveats.splice(0..0, caveats);
+
+        logger::log_audit(&format!(
+            "删除文件夹 {}（{}）: {}",
+            folder_path_str,
+            delete_mode(options.secure),
+            report.summary()
+        ));
+        for caveat in &report.caveats {
+            logger::log_audit(&format!("安全删除提示: {}", caveat));
+        }
+        Ok(report)
     } else {
         let error_msg = format!("路径不是目录: {}", folder_path_str);
         println!("{}", error_msg);
@@ -29,3 +411,98 @@ pub fn delete_folder(folder_path: &PathBuf) -> Result<(), String> {
         Err(error_msg)
     }
 }
+
+/// 重试删除一个失败的条目，返回删除的文件数、释放的空间和仍然失败的条目。
+/// 目录重试时报告其中所有失败的条目。
+/// 全部删除成功后会顺带清理 `root` 以内变为空的上级目录
+pub fn retry_failure(failure: &DeleteFailure, root: &Path, secure: bool) -> DeleteReport {
+    let path = &failure.path;
+    logger::log_info(&format!("重试删除: {}", path.display()));
+
+    let report = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => {
+            remove_dir_with_progress(path, secure, &AtomicBool::new(false), |_| {})
+        }
+        Ok(metadata) => match remove_file(path, secure) {
+            Ok(wiped) => DeleteReport {
+                files_removed: 1,
+                bytes_freed: metadata.len(),
+                caveats: not_wiped_caveat(u64::from(secure && !wiped))
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            },
+            Err(err) => DeleteReport {
+                failures: vec![DeleteFailure::new(path, &err)],
+                ..Default::default()
+            },
+        },
+        // 条目已经不存在，视为删除成功
+        Err(err) if err.kind() == io::ErrorKind::NotFound => DeleteReport::default(),
+        Err(err) => DeleteReport {
+            failures: vec![DeleteFailure::new(path, &err)],
+            ..Default::default()
+        },
+    };
+
+    if report.failures.is_empty() {
+        for dir in path.ancestors().skip(1) {
+            if !dir.starts_with(root) || fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+
+    report
+}
+
+// 删除单个文件，安全删除时先覆盖内容并重命名。
+// 返回内容是否已被覆盖，有其他硬链接的文件只删除链接、不覆盖
+fn remove_file(path: &Path, secure: bool) -> io::Result<bool> {
+    if secure {
+        let wiped = secure_delete::wipe_file(path)?;
+        fs::remove_file(&wiped.path)?;
+        Ok(wiped.overwritten)
+    } else {
+        fs::remove_file(path).map(|_| false)
+    }
+}
+
+fn not_wiped_caveat(count: u64) -> Option<String> {
+    (count > 0).then(|| {
+        format!(
+            "{} 个文件有其他硬链接或不是普通文件，只删除了链接，内容未被覆盖",
+            utils::format_count(count)
+        )
+    })
+}
+
+fn delete_mode(secure: bool) -> &'static str {
+    if secure {
+        "安全删除"
+    } else {
+        "普通删除"
+    }
+}
+
+// 把常见的系统错误转换为便于理解的原因
+fn describe_io_error(err: &io::Error) -> &'static str {
+    // Windows: ERROR_SHARING_VIOLATION / ERROR_LOCK_VIOLATION
+    #[cfg(windows)]
+    if matches!(err.raw_os_error(), Some(32) | Some(33)) {
+        return "被其他进程占用";
+    }
+    // Unix: EBUSY / ETXTBSY
+    #[cfg(unix)]
+    if matches!(err.raw_os_error(), Some(16) | Some(26)) {
+        return "被其他进程占用";
+    }
+
+    match err.kind() {
+        io::ErrorKind::PermissionDenied => "拒绝访问",
+        io::ErrorKind::NotFound => "文件不存在",
+        io::ErrorKind::DirectoryNotEmpty => "目录非空",
+        io::ErrorKind::ReadOnlyFilesystem => "只读文件系统",
+        _ => "其他错误",
+    }
+}
@@ -1,24 +1,528 @@
-use std::collections::HashSet;
-use std::fs::{self, OpenOptions};
-use std::io::{self, BufRead, Write};
-//use std::path::Path;
+use crate::app_dirs;
+use crate::logger;
+use eframe::egui;
+use globset::{GlobBuilder, GlobMatcher};
+use native_dialog::FileDialog;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
 
-const IGNORE_FILE: &str = "adcignore.txt";
+pub const IGNORE_FILE: &str = "ignore_list.yaml";
+pub const LEGACY_IGNORE_FILE: &str = "adcignore.txt"; // 旧版本的纯文本列表，启动时自动迁移
 
-pub fn load_ignored_folders() -> HashSet<String> {
-    let mut ignored = HashSet::new();
-    if let Ok(file) = fs::File::open(IGNORE_FILE) {
-        for line in io::BufReader::new(file).lines().flatten() {
-            ignored.insert(line);
+const ROOTS: [&str; 3] = ["Roaming", "Local", "LocalLow"];
+
+/// 忽略列表中的一条记录，`pattern` 的写法与 .gitignore 相同：
+/// `Microsoft*`、`!MicrosoftEdge`、`**/Cache`，`re:` 开头的按正则表达式匹配
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IgnoreEntry {
+    pub pattern: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>, // 只作用于这个根目录，为空时作用于所有根目录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub added: Option<String>,
+}
+
+impl IgnoreEntry {
+    /// 在当前时间忽略某个根目录下的文件夹
+    pub fn new(root: &str, pattern: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            root: Some(root.to_string()),
+            reason: None,
+            added: Some(chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()),
+        }
+    }
+}
+
+enum Matcher {
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+/// 编译好的忽略记录。规则无效时保留记录但不匹配任何文件夹
+pub struct IgnoreRule {
+    pub entry: IgnoreEntry,
+    negated: bool,
+    literal: String,
+    matcher: Option<Matcher>,
+}
+
+impl IgnoreRule {
+    pub fn new(entry: IgnoreEntry) -> Self {
+        let pattern = entry.pattern.trim();
+        let (negated, literal) = match pattern.strip_prefix('!') {
+            Some(rest) => (true, rest.to_string()),
+            None => (false, pattern.to_string()),
+        };
+        let matcher = compile(&literal)
+            .map_err(|e| logger::log_error(&format!("无效的忽略规则 {}: {}", entry.pattern, e)))
+            .ok();
+        Self {
+            entry,
+            negated,
+            literal,
+            matcher,
+        }
+    }
+
+    fn matches(&self, root: &str, folder: &str) -> bool {
+        if self.entry.root.as_deref().is_some_and(|r| r != root) {
+            return false;
+        }
+        // 旧版本按原名保存，像 {GUID} 这样的名称按通配符解析会变成别的意思
+        if self.literal == folder || (cfg!(windows) && self.literal.eq_ignore_ascii_case(folder)) {
+            return true;
+        }
+        match &self.matcher {
+            Some(Matcher::Glob(glob)) => glob.is_match(folder),
+            Some(Matcher::Regex(regex)) => regex.is_match(folder),
+            None => false,
+        }
+    }
+}
+
+// Windows 的文件名不区分大小写
+fn compile(pattern: &str) -> Result<Matcher, String> {
+    if pattern.is_empty() {
+        return Err("规则为空".to_string());
+    }
+    if let Some(expr) = pattern.strip_prefix("re:") {
+        let regex = RegexBuilder::new(&format!("^(?:{})$", expr))
+            .case_insensitive(cfg!(windows))
+            .build()
+            .map_err(|e| e.to_string())?;
+        return Ok(Matcher::Regex(regex));
+    }
+    let glob = GlobBuilder::new(pattern)
+        .case_insensitive(cfg!(windows))
+        .literal_separator(true)
+        .build()
+        .map_err(|e| e.to_string())?;
+    Ok(Matcher::Glob(glob.compile_matcher()))
+}
+
+/// 编译好的忽略列表，保持文件中的顺序
+#[derive(Default)]
+pub struct IgnoreList {
+    rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreList {
+    pub fn from_entries(entries: Vec<IgnoreEntry>) -> Self {
+        Self {
+            rules: entries.into_iter().map(IgnoreRule::new).collect(),
+        }
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &IgnoreEntry> {
+        self.rules.iter().map(|rule| &rule.entry)
+    }
+
+    /// 决定文件夹是否被忽略的规则。与 .gitignore 一样以最后一条匹配的规则为准，
+    /// 最后匹配的是 `!` 规则时不忽略
+    pub fn matching_rule(&self, root: &str, folder: &str) -> Option<&IgnoreRule> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(root, folder))
+            .filter(|rule| !rule.negated)
+    }
+
+    pub fn add(&mut self, entry: IgnoreEntry) {
+        self.rules.push(IgnoreRule::new(entry));
+    }
+
+    pub fn remove(&mut self, index: usize) -> IgnoreEntry {
+        self.rules.remove(index).entry
+    }
+
+    pub fn set_reason(&mut self, index: usize, reason: Option<String>) {
+        self.rules[index].entry.reason = reason;
+    }
+
+    /// 导入时跳过根目录和规则都相同的记录，返回新增的条数
+    pub fn merge(&mut self, entries: Vec<IgnoreEntry>) -> usize {
+        let mut added = 0;
+        for entry in entries {
+            let exists = self
+                .entries()
+                .any(|e| e.pattern == entry.pattern && e.root == entry.root);
+            if !exists {
+                self.add(entry);
+                added += 1;
+            }
+        }
+        added
+    }
+}
+
+/// 解析旧版本的 adcignore.txt：每行一个名称或规则，`#` 开头为注释，
+/// `Local:NVIDIA*` 这样的前缀表示只作用于该根目录
+pub fn parse_legacy(text: &str) -> Vec<IgnoreEntry> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (negation, rest) = match line.strip_prefix('!') {
+                Some(rest) => ("!", rest),
+                None => ("", line),
+            };
+            let (root, pattern) = match rest.split_once(':') {
+                Some((root, pattern)) if ROOTS.contains(&root) => (Some(root.to_string()), pattern),
+                _ => (None, rest),
+            };
+            IgnoreEntry {
+                pattern: format!("{}{}", negation, pattern),
+                root,
+                reason: None,
+                added: None,
+            }
+        })
+        .collect()
+}
+
+pub fn load_ignore_list() -> IgnoreList {
+    IgnoreList::from_entries(load_entries(
+        &app_dirs::config_file(IGNORE_FILE),
+        &app_dirs::config_file(LEGACY_IGNORE_FILE),
+    ))
+}
+
+fn load_entries(path: &Path, legacy_path: &Path) -> Vec<IgnoreEntry> {
+    if let Ok(content) = fs::read_to_string(path) {
+        return serde_yaml::from_str(&content).unwrap_or_else(|e| {
+            logger::log_error(&format!("解析忽略列表失败: {}", e));
+            app_dirs::set_aside_unparsable(path);
+            Vec::new()
+        });
+    }
+
+    // 第一次启动新版本时迁移旧列表，原文件改名保留
+    let Ok(content) = fs::read_to_string(legacy_path) else {
+        return Vec::new();
+    };
+    let entries = parse_legacy(&content);
+    if let Err(e) = save_entries(path, &entries) {
+        logger::log_error(&e);
+    } else {
+        let backup = legacy_path.with_extension("txt.bak");
+        if let Err(e) = fs::rename(legacy_path, &backup) {
+            logger::log_error(&format!("无法重命名旧的忽略列表: {}", e));
+        }
+        logger::log_info(&format!(
+            "已把 {} 条忽略规则从 {} 迁移到 {}",
+            entries.len(),
+            legacy_path.display(),
+            path.display()
+        ));
+    }
+    entries
+}
+
+pub fn save_ignore_list(list: &IgnoreList) {
+    let entries: Vec<IgnoreEntry> = list.entries().cloned().collect();
+    if let Err(e) = save_entries(&app_dirs::config_file(IGNORE_FILE), &entries) {
+        logger::log_error(&e);
+    }
+}
+
+fn save_entries(path: &Path, entries: &[IgnoreEntry]) -> Result<(), String> {
+    let content =
+        serde_yaml::to_string(entries).map_err(|e| format!("序列化忽略列表失败: {}", e))?;
+    fs::write(path, content).map_err(|e| format!("保存忽略列表失败: {}", e))
+}
+
+/// 从其他电脑导出的列表导入，`.txt` 按旧版本的 adcignore.txt 解析
+pub fn import_entries(path: &Path) -> Result<Vec<IgnoreEntry>, String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("无法读取 {}: {}", path.display(), e))?;
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("txt"))
+    {
+        return Ok(parse_legacy(&content));
+    }
+    serde_yaml::from_str(&content).map_err(|e| format!("解析 {} 失败: {}", path.display(), e))
+}
+
+pub fn export_entries(path: &Path, list: &IgnoreList) -> Result<(), String> {
+    let entries: Vec<IgnoreEntry> = list.entries().cloned().collect();
+    save_entries(path, &entries)
+}
+
+/// “忽略列表”窗口，管理所有忽略记录
+#[derive(Default)]
+pub struct IgnoreWindow {
+    pub show_window: bool,
+    search: String,
+    editing: Option<(usize, String)>, // 正在编辑原因的记录和输入框内容
+    status_message: Option<String>,
+}
+
+enum IgnoreAction {
+    Remove(usize),
+    SaveReason(usize, String),
+    Import,
+    Export,
+}
+
+impl IgnoreWindow {
+    pub fn open(&mut self) {
+        self.show_window = true;
+        self.editing = None;
+        self.status_message = None;
+    }
+
+    // 规则、根目录和原因中包含搜索内容的记录
+    fn matches_search(&self, entry: &IgnoreEntry) -> bool {
+        let search = self.search.trim().to_lowercase();
+        search.is_empty()
+            || [
+                Some(&entry.pattern),
+                entry.root.as_ref(),
+                entry.reason.as_ref(),
+            ]
+            .into_iter()
+            .flatten()
+            .any(|text| text.to_lowercase().contains(&search))
+    }
+
+    pub fn show_ignore_window(&mut self, ctx: &egui::Context, list: &mut IgnoreList) {
+        if !self.show_window {
+            return;
+        }
+
+        let mut open = true;
+        let mut action = None;
+        egui::Window::new("忽略列表")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("搜索:");
+                    ui.text_edit_singleline(&mut self.search);
+                    if ui.button("导入").clicked() {
+                        action = Some(IgnoreAction::Import);
+                    }
+                    if ui.button("导出").clicked() {
+                        action = Some(IgnoreAction::Export);
+                    }
+                });
+
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("ignore_entries")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.label("规则");
+                                ui.label("根目录");
+                                ui.label("原因");
+                                ui.label("添加时间");
+                                ui.label("操作");
+                                ui.end_row();
+
+                                for (index, rule) in list.rules.iter().enumerate() {
+                                    let entry = &rule.entry;
+                                    if !self.matches_search(entry) {
+                                        continue;
+                                    }
+                                    if rule.matcher.is_some() {
+                                        ui.label(&entry.pattern);
+                                    } else {
+                                        ui.colored_label(egui::Color32::RED, &entry.pattern)
+                                            .on_hover_text("规则无效，不会忽略任何文件夹");
+                                    }
+                                    ui.label(entry.root.as_deref().unwrap_or("全部"));
+                                    match &mut self.editing {
+                                        Some((editing, reason)) if *editing == index => {
+                                            ui.text_edit_singleline(reason);
+                                        }
+                                        _ => {
+                                            ui.label(entry.reason.as_deref().unwrap_or(""));
+                                        }
+                                    }
+                                    ui.label(entry.added.as_deref().unwrap_or(""));
+                                    ui.horizontal(|ui| {
+                                        match &self.editing {
+                                            Some((editing, reason)) if *editing == index => {
+                                                if ui.button("保存").clicked() {
+                                                    action = Some(IgnoreAction::SaveReason(
+                                                        index,
+                                                        reason.clone(),
+                                                    ));
+                                                }
+                                                if ui.button("取消").clicked() {
+                                                    self.editing = None;
+                                                }
+                                            }
+                                            _ => {
+                                                if ui.button("编辑原因").clicked() {
+                                                    self.editing = Some((
+                                                        index,
+                                                        entry.reason.clone().unwrap_or_default(),
+                                                    ));
+                                                }
+                                            }
+                                        }
+                                        if ui.button("取消忽略").clicked() {
+                                            action = Some(IgnoreAction::Remove(index));
+                                        }
+                                    });
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                if let Some(message) = &self.status_message {
+                    ui.label(message);
+                }
+            });
+        if !open {
+            self.show_window = false;
+        }
+
+        match action {
+            Some(IgnoreAction::Remove(index)) => {
+                let entry = list.remove(index);
+                save_ignore_list(list);
+                self.editing = None;
+                let message = format!("已取消忽略: {}", entry.pattern);
+                logger::log_info(&message);
+                self.status_message = Some(message);
+            }
+            Some(IgnoreAction::SaveReason(index, reason)) => {
+                let reason = reason.trim();
+                list.set_reason(index, (!reason.is_empty()).then(|| reason.to_string()));
+                save_ignore_list(list);
+                self.editing = None;
+            }
+            Some(IgnoreAction::Import) => {
+                if let Ok(Some(path)) = FileDialog::new()
+                    .add_filter("忽略列表", &["yaml", "yml", "txt"])
+                    .show_open_single_file()
+                {
+                    let message = match import_entries(&path) {
+                        Ok(entries) => {
+                            let count = list.merge(entries);
+                            save_ignore_list(list);
+                            format!("已从 {} 导入 {} 条规则", path.display(), count)
+                        }
+                        Err(e) => e,
+                    };
+                    logger::log_info(&message);
+                    self.status_message = Some(message);
+                }
+            }
+            Some(IgnoreAction::Export) => {
+                if let Ok(Some(path)) = FileDialog::new()
+                    .add_filter("忽略列表", &["yaml"])
+                    .set_filename(IGNORE_FILE)
+                    .show_save_single_file()
+                {
+                    let message = match export_entries(&path, list) {
+                        Ok(()) => format!("已导出到 {}", path.display()),
+                        Err(e) => e,
+                    };
+                    logger::log_info(&message);
+                    self.status_message = Some(message);
+                }
+            }
+            None => {}
         }
     }
-    ignored
 }
 
-pub fn save_ignored_folders(ignored: &HashSet<String>) {
-    if let Ok(mut file) = OpenOptions::new().write(true).create(true).truncate(true).open(IGNORE_FILE) {
-        for folder in ignored {
-            writeln!(file, "{}", folder).unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl IgnoreList {
+        fn is_ignored(&self, root: &str, folder: &str) -> bool {
+            self.matching_rule(root, folder).is_some()
         }
     }
+
+    #[test]
+    fn last_matching_rule_wins_and_roots_are_respected() {
+        let list = IgnoreList::from_entries(parse_legacy(
+            "# 注释\nMicrosoft*\n!MicrosoftEdge\n**/Cache\nLocal:NVIDIA*\nre:[0-9]+\\.tmp\n{1234-ABCD}\n",
+        ));
+
+        assert_eq!(
+            list.matching_rule("Roaming", "MicrosoftTeams")
+                .map(|r| r.entry.pattern.as_str()),
+            Some("Microsoft*")
+        );
+        assert!(!list.is_ignored("Roaming", "MicrosoftEdge"));
+        assert!(list.is_ignored("Roaming", "Cache"));
+        assert!(list.is_ignored("Local", "NVIDIA Corporation"));
+        assert!(!list.is_ignored("Roaming", "NVIDIA Corporation"));
+        assert!(list.is_ignored("Local", "42.tmp"));
+        assert!(!list.is_ignored("Local", "a42.tmp"));
+        assert!(list.is_ignored("Local", "{1234-ABCD}"));
+        assert!(!list.is_ignored("Local", "# 注释"));
+    }
+
+    #[test]
+    fn legacy_list_is_migrated_once() {
+        let dir = std::env::temp_dir().join(format!("adc_ignore_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ignore_list.yaml");
+        let legacy = dir.join("adcignore.txt");
+        fs::write(&legacy, "Temp\nLocal:NVIDIA*\n").unwrap();
+
+        let entries = load_entries(&path, &legacy);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].root.as_deref(), Some("Local"));
+        assert_eq!(entries[1].pattern, "NVIDIA*");
+        assert!(!legacy.exists());
+        assert!(dir.join("adcignore.txt.bak").exists());
+        assert_eq!(load_entries(&path, &legacy), entries);
+
+        // 新的忽略只作用于当前根目录
+        let mut list = IgnoreList::from_entries(entries);
+        list.add(IgnoreEntry::new("Local", "Cache"));
+        assert!(list.is_ignored("Local", "Cache"));
+        assert!(!list.is_ignored("Roaming", "Cache"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unparsable_list_is_kept_as_backup() {
+        let dir = std::env::temp_dir().join(format!("adc_ignore_broken_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ignore_list.yaml");
+        fs::write(&path, "- pattern: [unclosed\n").unwrap();
+
+        assert!(load_entries(&path, &dir.join("adcignore.txt")).is_empty());
+        assert!(!path.exists());
+        assert_eq!(
+            fs::read_to_string(dir.join("ignore_list.yaml.bak")).unwrap(),
+            "- pattern: [unclosed\n"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn import_skips_existing_entries() {
+        let mut list = IgnoreList::from_entries(vec![IgnoreEntry::new("Local", "Temp")]);
+
+        let added = list.merge(parse_legacy("Local:Temp\nRoaming:Temp\n"));
+
+        assert_eq!(added, 1);
+        assert_eq!(list.entries().count(), 2);
+        assert_eq!(list.remove(0).root.as_deref(), Some("Local"));
+        assert!(!list.is_ignored("Local", "Temp"));
+        assert!(list.is_ignored("Roaming", "Temp"));
+    }
 }
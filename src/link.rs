@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 移动文件夹后在原位置创建的链接类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LinkKind {
+    Junction, // 目录联接，仅 Windows，不需要管理员权限
+    Symlink,  // 符号链接，Windows 上需要管理员权限或开发者模式
+}
+
+impl LinkKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            LinkKind::Junction => "目录联接",
+            LinkKind::Symlink => "符号链接",
+        }
+    }
+
+    /// 当前系统可用的链接类型，第一个为默认值
+    pub fn available() -> &'static [LinkKind] {
+        if cfg!(windows) {
+            &[LinkKind::Junction, LinkKind::Symlink]
+        } else {
+            &[LinkKind::Symlink]
+        }
+    }
+}
+
+impl Default for LinkKind {
+    fn default() -> Self {
+        Self::available()[0]
+    }
+}
+
+/// 创建目录链接的方式。移动流程只依赖这个接口，便于替换和测试
+pub trait LinkStrategy {
+    fn kind(&self) -> LinkKind;
+
+    /// 在 `link` 处创建指向 `target` 的目录链接，`target` 必须已存在，`link` 必须不存在
+    fn create(&self, link: &Path, target: &Path) -> Result<(), String>;
+}
+
+pub struct JunctionStrategy;
+pub struct SymlinkStrategy;
+
+pub fn strategy_for(kind: LinkKind) -> Box<dyn LinkStrategy> {
+    match kind {
+        LinkKind::Junction => Box::new(JunctionStrategy),
+        LinkKind::Symlink => Box::new(SymlinkStrategy),
+    }
+}
+
+// 链接中的相对路径是相对于链接所在目录解析的，而不是当前工作目录，
+// 所以统一写入规范化后的绝对路径
+fn absolute_target(target: &Path) -> Result<PathBuf, String> {
+    let target = fs::canonicalize(target)
+        .map_err(|err| format!("无法解析目标路径 {}: {}", target.display(), err))?;
+
+    // Windows 的规范路径带有 \\?\ 前缀，链接中写入普通路径
+    #[cfg(windows)]
+    {
+        let text = target.to_string_lossy();
+        if let Some(rest) = text.strip_prefix(r"\\?\UNC\") {
+            return Ok(PathBuf::from(format!(r"\\{}", rest)));
+        }
+        if let Some(rest) = text.strip_prefix(r"\\?\") {
+            return Ok(PathBuf::from(rest));
+        }
+    }
+
+    Ok(target)
+}
+
+impl LinkStrategy for SymlinkStrategy {
+    fn kind(&self) -> LinkKind {
+        LinkKind::Symlink
+    }
+
+    fn create(&self, link: &Path, target: &Path) -> Result<(), String> {
+        let target = absolute_target(target)?;
+
+        #[cfg(unix)]
+        let result = std::os::unix::fs::symlink(&target, link);
+        #[cfg(windows)]
+        let result = std::os::windows::fs::symlink_dir(&target, link);
+        #[cfg(not(any(unix, windows)))]
+        let result: io::Result<()> = Err(io::Error::from(io::ErrorKind::Unsupported));
+
+        result.map_err(|err| {
+            // ERROR_PRIVILEGE_NOT_HELD
+            if cfg!(windows) && err.raw_os_error() == Some(1314) {
+                "创建符号链接需要管理员权限或开启开发者模式，可以改用目录联接".to_string()
+            } else {
+                describe_error(self.kind(), &err)
+            }
+        })
+    }
+}
+
+impl LinkStrategy for JunctionStrategy {
+    fn kind(&self) -> LinkKind {
+        LinkKind::Junction
+    }
+
+    #[cfg(windows)]
+    fn create(&self, link: &Path, target: &Path) -> Result<(), String> {
+        let target = absolute_target(target)?;
+        if target.to_string_lossy().starts_with(r"\\") {
+            return Err("目录联接不能指向网络位置，请改用符号链接".to_string());
+        }
+        create_junction(link, &target).map_err(|err| describe_error(self.kind(), &err))
+    }
+
+    #[cfg(not(windows))]
+    fn create(&self, _link: &Path, _target: &Path) -> Result<(), String> {
+        Err("目录联接只能在 Windows 上使用，请改用符号链接".to_string())
+    }
+}
+
+fn describe_error(kind: LinkKind, err: &io::Error) -> String {
+    match err.kind() {
+        io::ErrorKind::AlreadyExists => format!("创建{}失败: 原路径已存在", kind.label()),
+        io::ErrorKind::PermissionDenied => format!("创建{}失败: 拒绝访问", kind.label()),
+        _ => format!("创建{}失败: {}", kind.label(), err),
+    }
+}
+
+// 创建空目录后写入挂载点重解析数据，与 mklink /J 的效果相同
+#[cfg(windows)]
+fn create_junction(link: &Path, target: &Path) -> io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+    use std::ptr;
+    use windows_sys::Win32::Storage::FileSystem::{
+        FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT, MAXIMUM_REPARSE_DATA_BUFFER_SIZE,
+    };
+    use windows_sys::Win32::System::Ioctl::FSCTL_SET_REPARSE_POINT;
+    use windows_sys::Win32::System::IO::DeviceIoControl;
+
+    const IO_REPARSE_TAG_MOUNT_POINT: u32 = 0xA000_0003;
+
+    // 替代名使用 NT 路径 \??\C:\...，显示名使用普通路径
+    let print_name: Vec<u16> = target.as_os_str().encode_wide().collect();
+    let substitute_name: Vec<u16> = r"\??\"
+        .encode_utf16()
+        .chain(print_name.iter().copied())
+        .collect();
+    let substitute_bytes = (substitute_name.len() * 2) as u16;
+    let print_bytes = (print_name.len() * 2) as u16;
+
+    let mut path_buffer = substitute_name;
+    path_buffer.push(0);
+    path_buffer.extend(&print_name);
+    path_buffer.push(0);
+
+    // REPARSE_DATA_BUFFER 中 MountPointReparseBuffer 的布局
+    let data_length = (8 + path_buffer.len() * 2) as u16;
+    let mut data = Vec::with_capacity(8 + data_length as usize);
+    data.extend(IO_REPARSE_TAG_MOUNT_POINT.to_le_bytes());
+    data.extend(data_length.to_le_bytes());
+    data.extend(0u16.to_le_bytes()); // 保留
+    data.extend(0u16.to_le_bytes()); // 替代名偏移
+    data.extend(substitute_bytes.to_le_bytes());
+    data.extend((substitute_bytes + 2).to_le_bytes()); // 显示名偏移，跳过替代名结尾的 0
+    data.extend(print_bytes.to_le_bytes());
+    for unit in path_buffer {
+        data.extend(unit.to_le_bytes());
+    }
+    if data.len() > MAXIMUM_REPARSE_DATA_BUFFER_SIZE as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "目标路径过长"));
+    }
+
+    fs::create_dir(link)?;
+    let result = fs::OpenOptions::new()
+        .write(true)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT)
+        .open(link)
+        .and_then(|dir| {
+            let mut returned = 0u32;
+            let ok = unsafe {
+                DeviceIoControl(
+                    dir.as_raw_handle(),
+                    FSCTL_SET_REPARSE_POINT,
+                    data.as_ptr().cast(),
+                    data.len() as u32,
+                    ptr::null_mut(),
+                    0,
+                    &mut returned,
+                    ptr::null_mut(),
+                )
+            };
+            if ok == 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        });
+
+    // 写入失败时删除刚创建的空目录，避免留下普通文件夹
+    if result.is_err() {
+        let _ = fs::remove_dir(link);
+    }
+    result
+}
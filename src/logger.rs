@@ -1,7 +1,12 @@
+use crate::app_dirs;
 use simplelog::{Config, LevelFilter, SimpleLogger, WriteLogger};
-use std::fs::File;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
 use std::sync::Once;
 
+pub const LOG_FILE: &str = "appdata_cleaner.log";
+pub const AUDIT_LOG_FILE: &str = "appdata_cleaner_audit.log";
+
 static INIT_LOGGER: Once = Once::new(); // 确保日志系统只初始化一次
 
 pub fn init_logger(log_to_file: bool) {
@@ -10,7 +15,7 @@ pub fn init_logger(log_to_file: bool) {
             let _ = WriteLogger::init(
                 LevelFilter::Info,
                 Config::default(),
-                File::create("appdata_cleaner.log").expect("无法创建日志文件"),
+                File::create(app_dirs::log_file(LOG_FILE)).expect("无法创建日志文件"),
             );
         } else {
             let _ = SimpleLogger::init(LevelFilter::Info, Config::default());
@@ -25,3 +30,22 @@ pub fn log_info(message: &str) {
 pub fn log_error(message: &str) {
     log::error!("{}", message);
 }
+
+/// 审计日志：记录删除等不可逆操作，不受"启用日志"开关影响，只追加不覆盖
+pub fn log_audit(message: &str) {
+    log::info!("[审计] {}", message);
+
+    let line = format!(
+        "{} {}\n",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+        message
+    );
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(app_dirs::log_file(AUDIT_LOG_FILE))
+        .and_then(|mut file| file.write_all(line.as_bytes()));
+    if let Err(e) = result {
+        log::error!("无法写入审计日志: {}", e);
+    }
+}
@@ -1,28 +1,52 @@
 mod about; // 关于界面
+mod age_cleanup; // 按时间清理过期文件
+mod app_dirs; // 配置、日志和缓存目录，支持便携模式
+mod archive; // 备份为压缩包后删除
 mod confirmation; // 确认删除模块
+mod content_cleaner; // 清理文件夹中的缓存和日志
 mod delete; // 引入删除模块
 mod ignore; // 引入忽略模块
+mod link; // 移动后创建目录联接或符号链接
 mod logger; // 引入日志模块
+mod move_checkpoint; // 跨卷复制的断点记录，用于继续中断的移动
+mod move_journal; // 移动操作日志，用于回滚和崩溃恢复
 mod move_module; // 移动文件夹，使用 mklink 指令
 mod open; // 调用资源管理器打开文件夹
+mod process_check; // 检测正在使用文件夹的进程
+mod protected; // 受保护文件夹列表
+mod relocation; // 已移动文件夹的记录和链接状态
+mod restore; // 从备份压缩包还原
+mod rule_sources; // 描述规则的订阅源
 mod scanner; // 引入扫盘模块
+mod secure_delete; // 安全删除，覆盖文件内容
+mod settings; // 保存界面和行为设置
 mod ui; // 引入 ui 模块
 mod utils; // 文件夹大小计算模块
 mod yaml_loader; // 文件描述
 
+use eframe::egui;
 use ui::AppDataCleaner;
 
 fn main() -> Result<(), eframe::Error> {
+    // 旧版本的文件保存在工作目录中，要在创建新的日志文件之前迁移
+    let migration_notes = app_dirs::migrate_working_dir_files();
     // 初始化日志
     logger::init_logger(true); // true 表示日志记录到文件，false 表示只输出到控制台
+    for note in &migration_notes {
+        logger::log_info(note);
+    }
 
-    let options = eframe::NativeOptions::default();
+    let settings = settings::load_settings();
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size(settings.window_size),
+        ..Default::default()
+    };
     eframe::run_native(
         "AppData 清理器",
         options,
         Box::new(|_| {
             logger::log_info("应用程序启动");
-            Ok(Box::new(AppDataCleaner::default()))
+            Ok(Box::new(AppDataCleaner::new(settings)))
         }),
     )
 }
@@ -0,0 +1,142 @@
+use crate::app_dirs;
+use crate::logger;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+pub const MOVE_CHECKPOINT_FILE: &str = "move_checkpoint.txt";
+
+fn checkpoint_path() -> PathBuf {
+    app_dirs::config_file(MOVE_CHECKPOINT_FILE)
+}
+
+/// 已复制完成的文件。源文件的大小和修改时间都没变时，再次移动会跳过它
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointEntry {
+    pub size: u64,
+    pub modified: (i64, u32), // 源文件的修改时间，Unix 秒数和纳秒
+    pub sha256: String,
+}
+
+impl CheckpointEntry {
+    pub fn new(metadata: &fs::Metadata, sha256: String) -> Self {
+        let modified = filetime::FileTime::from_last_modification_time(metadata);
+        Self {
+            size: metadata.len(),
+            modified: (modified.unix_seconds(), modified.nanoseconds()),
+            sha256,
+        }
+    }
+
+    /// 源文件自记录以来没有变化
+    pub fn matches(&self, metadata: &fs::Metadata) -> bool {
+        let modified = filetime::FileTime::from_last_modification_time(metadata);
+        self.size == metadata.len()
+            && self.modified == (modified.unix_seconds(), modified.nanoseconds())
+    }
+}
+
+/// 跨卷复制的断点记录。前两行为源路径和目标路径，之后每复制完一个文件追加一行
+/// `sha256\t大小\t秒\t纳秒\t相对路径`，程序中途退出时最多丢失最后一行
+pub struct MoveCheckpoint {
+    entries: HashMap<PathBuf, CheckpointEntry>,
+    file: File,
+}
+
+// 读取断点文件，源路径和目标路径与本次移动一致时才返回记录
+fn read_entries(source: &Path, target: &Path) -> Option<HashMap<PathBuf, CheckpointEntry>> {
+    let file = File::open(checkpoint_path()).ok()?;
+    let mut lines = BufReader::new(file).lines().map_while(Result::ok);
+    if lines.next()? != format!("source\t{}", source.display())
+        || lines.next()? != format!("target\t{}", target.display())
+    {
+        return None;
+    }
+
+    let mut entries = HashMap::new();
+    for line in lines {
+        let fields: Vec<&str> = line.splitn(5, '\t').collect();
+        let [sha256, size, seconds, nanos, path] = fields[..] else {
+            continue;
+        };
+        let (Ok(size), Ok(seconds), Ok(nanos)) = (size.parse(), seconds.parse(), nanos.parse())
+        else {
+            continue;
+        };
+        entries.insert(
+            PathBuf::from(path),
+            CheckpointEntry {
+                size,
+                modified: (seconds, nanos),
+                sha256: sha256.to_string(),
+            },
+        );
+    }
+    Some(entries)
+}
+
+/// 是否有同一次移动留下的断点，有时允许目标目录不为空
+pub fn exists_for(source: &Path, target: &Path) -> bool {
+    read_entries(source, target).is_some()
+}
+
+/// 删除属于这次移动的断点，其他移动留下的断点保持不变
+pub fn clear_for(source: &Path, target: &Path) {
+    if exists_for(source, target) {
+        if let Err(e) = fs::remove_file(checkpoint_path()) {
+            logger::log_error(&format!("无法删除移动断点: {}", e));
+        }
+    }
+}
+
+impl MoveCheckpoint {
+    /// 继续同一次移动的断点，否则重新开始记录
+    pub fn open(source: &Path, target: &Path) -> Result<Self, String> {
+        let to_error = |e: io::Error| format!("无法写入移动断点: {}", e);
+        if let Some(entries) = read_entries(source, target) {
+            let file = OpenOptions::new()
+                .append(true)
+                .open(checkpoint_path())
+                .map_err(to_error)?;
+            return Ok(Self { entries, file });
+        }
+
+        let mut file = File::create(checkpoint_path()).map_err(to_error)?;
+        writeln!(
+            file,
+            "source\t{}\ntarget\t{}",
+            source.display(),
+            target.display()
+        )
+        .map_err(to_error)?;
+        Ok(Self {
+            entries: HashMap::new(),
+            file,
+        })
+    }
+
+    pub fn get(&self, relative: &Path) -> Option<&CheckpointEntry> {
+        self.entries.get(relative)
+    }
+
+    /// 已记录文件的总大小，用于估算还需要的磁盘空间
+    pub fn copied_bytes(&self) -> u64 {
+        self.entries.values().map(|e| e.size).sum()
+    }
+
+    pub fn record(&mut self, relative: &Path, entry: CheckpointEntry) -> Result<(), String> {
+        writeln!(
+            self.file,
+            "{}\t{}\t{}\t{}\t{}",
+            entry.sha256,
+            entry.size,
+            entry.modified.0,
+            entry.modified.1,
+            relative.display()
+        )
+        .map_err(|e| format!("无法写入移动断点: {}", e))?;
+        self.entries.insert(relative.to_path_buf(), entry);
+        Ok(())
+    }
+}
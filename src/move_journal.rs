@@ -0,0 +1,333 @@
+use crate::app_dirs;
+use crate::link::{self, LinkKind};
+use crate::logger;
+use crate::move_checkpoint;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const MOVE_JOURNAL_FILE: &str = "move_journal.yaml";
+
+pub fn journal_path() -> PathBuf {
+    app_dirs::config_file(MOVE_JOURNAL_FILE)
+}
+
+/// 移动进行到的阶段，决定回滚或恢复时需要撤销哪些步骤
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// 移回时沿用同样的阶段，含义见各阶段说明
+pub enum MoveStage {
+    Copying,       // 正在复制到目标目录（移回时为原位置旁的临时目录），原数据未改动
+    Verifying,     // 复制完成，正在校验
+    SourceRenamed, // 源文件夹已改名为备份或目标，链接可能已创建；移回时链接已删除
+    Linked,        // 链接已创建并验证，只剩删除备份；移回时数据已回到原位，只剩删除目标目录
+}
+
+/// 移动操作日志。每进入一个阶段都会写入磁盘，程序中途退出后可据此恢复
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveJournal {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub backup: PathBuf,
+    pub target_created: bool, // 目标目录是否由本次移动创建
+    #[serde(default)]
+    pub renamed: bool, // 同一个卷上直接把源文件夹重命名到目标位置，没有备份
+    #[serde(default)]
+    pub move_back: bool, // 把已移动的文件夹移回原位置，source 为链接所在的原路径
+    pub stage: MoveStage,
+    pub started: String,
+}
+
+/// 移动过程中源文件夹的临时备份名，移回时作为复制数据的临时目录
+pub fn backup_path(source: &Path) -> PathBuf {
+    let name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    source.with_file_name(format!("{}.adc-move-backup", name))
+}
+
+/// 删除链接本身而不影响链接指向的内容
+pub fn remove_link(path: &Path) -> io::Result<()> {
+    // Windows 上目录链接需要用 remove_dir 删除
+    fs::remove_file(path).or_else(|_| fs::remove_dir(path))
+}
+
+pub fn is_link(path: &Path) -> bool {
+    fs::symlink_metadata(path)
+        .map(|m| m.file_type().is_symlink())
+        .unwrap_or(false)
+}
+
+impl MoveJournal {
+    pub fn new(source: &Path, target: &Path, target_created: bool, renamed: bool) -> Self {
+        Self {
+            source: source.to_path_buf(),
+            target: target.to_path_buf(),
+            backup: backup_path(source),
+            target_created,
+            renamed,
+            move_back: false,
+            stage: MoveStage::Copying,
+            started: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        }
+    }
+
+    /// 移回操作的日志，`source` 为链接所在的原路径，`target` 为数据当前所在的目录
+    pub fn new_move_back(source: &Path, target: &Path, renamed: bool) -> Self {
+        Self {
+            move_back: true,
+            ..Self::new(source, target, false, renamed)
+        }
+    }
+
+    pub fn load() -> Option<Self> {
+        let content = fs::read_to_string(journal_path()).ok()?;
+        serde_yaml::from_str(&content)
+            .map_err(|e| logger::log_error(&format!("解析移动日志失败: {}", e)))
+            .ok()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let content =
+            serde_yaml::to_string(self).map_err(|e| format!("序列化移动日志失败: {}", e))?;
+        fs::write(journal_path(), content).map_err(|e| format!("无法写入移动日志: {}", e))
+    }
+
+    /// 进入下一阶段，必须在执行该阶段的操作之前调用
+    pub fn set_stage(&mut self, stage: MoveStage) -> Result<(), String> {
+        self.stage = stage;
+        self.save()
+    }
+
+    pub fn clear() {
+        if let Err(e) = fs::remove_file(journal_path()) {
+            if e.kind() != io::ErrorKind::NotFound {
+                logger::log_error(&format!("无法删除移动日志: {}", e));
+            }
+        }
+    }
+
+    /// 撤销已执行的步骤，让源文件夹回到原位并清空目标目录。
+    /// 每一步都可以重复执行，回滚中途退出也能再次回滚
+    pub fn rollback(&self) -> Result<(), String> {
+        if self.move_back {
+            self.undo_move_back()?;
+        } else if self.renamed {
+            self.undo_rename()?;
+        } else {
+            self.undo_copy()?;
+        }
+
+        Self::clear();
+        logger::log_audit(&format!(
+            "已回滚{} {} -> {}",
+            self.action(),
+            self.source.display(),
+            self.target.display()
+        ));
+        Ok(())
+    }
+
+    // 删除链接，把目标目录改回原名，并恢复原来的空目标目录
+    fn undo_rename(&self) -> Result<(), String> {
+        if is_link(&self.source) {
+            remove_link(&self.source).map_err(|e| format!("无法删除链接: {}", e))?;
+        }
+        if !self.source.exists() && self.target.exists() {
+            fs::rename(&self.target, &self.source)
+                .map_err(|e| format!("无法还原源文件夹: {}", e))?;
+        }
+        if !self.source.exists() {
+            return Err(format!("源文件夹不在原位: {}", self.source.display()));
+        }
+        if !self.target_created && !self.target.exists() {
+            fs::create_dir(&self.target).map_err(|e| format!("无法恢复目标目录: {}", e))?;
+        } else if self.target_created {
+            // 本次创建的空目标目录，重命名前退出时会留下
+            let _ = fs::remove_dir(&self.target);
+        }
+        Ok(())
+    }
+
+    fn undo_copy(&self) -> Result<(), String> {
+        if self.stage == MoveStage::SourceRenamed || self.stage == MoveStage::Linked {
+            self.restore_source()?;
+        }
+
+        // 源文件夹已在原位，目标目录中的数据只是副本
+        if !self.source.exists() {
+            return Err(format!("源文件夹不在原位: {}", self.source.display()));
+        }
+        self.clear_target()?;
+        move_checkpoint::clear_for(&self.source, &self.target);
+        Ok(())
+    }
+
+    fn action(&self) -> &'static str {
+        if self.move_back {
+            "移回"
+        } else {
+            "移动"
+        }
+    }
+
+    // 把已经放回原位的数据撤走，删除临时目录，再重新创建指向目标目录的链接
+    fn undo_move_back(&self) -> Result<(), String> {
+        let restored = self.source.exists() && !is_link(&self.source);
+        if restored && self.stage != MoveStage::Copying && self.stage != MoveStage::Verifying {
+            let back_to = if self.renamed {
+                &self.target
+            } else {
+                &self.backup
+            };
+            if back_to.exists() {
+                return Err(format!(
+                    "原路径和{}同时存在，请手动检查: {} / {}",
+                    if self.renamed {
+                        "目标目录"
+                    } else {
+                        "临时目录"
+                    },
+                    self.source.display(),
+                    back_to.display()
+                ));
+            }
+            fs::rename(&self.source, back_to)
+                .map_err(|e| format!("无法撤回原路径的数据: {}", e))?;
+        }
+
+        // 临时目录中只是副本，目标目录中的数据始终完整
+        if !self.renamed && self.backup.exists() {
+            fs::remove_dir_all(&self.backup).map_err(|e| format!("无法删除临时目录: {}", e))?;
+        }
+        if !self.target.exists() {
+            return Err(format!("目标目录不存在: {}", self.target.display()));
+        }
+        if !is_link(&self.source) {
+            relink(&self.source, &self.target)?;
+        }
+        Ok(())
+    }
+
+    // 删除链接并把备份改回原名
+    fn restore_source(&self) -> Result<(), String> {
+        if !self.backup.exists() {
+            return Ok(());
+        }
+        if is_link(&self.source) {
+            remove_link(&self.source).map_err(|e| format!("无法删除链接: {}", e))?;
+        }
+        if self.source.exists() {
+            return Err(format!(
+                "源路径和备份同时存在，请手动检查: {} / {}",
+                self.source.display(),
+                self.backup.display()
+            ));
+        }
+        fs::rename(&self.backup, &self.source).map_err(|e| format!("无法还原源文件夹: {}", e))
+    }
+
+    fn clear_target(&self) -> Result<(), String> {
+        let Ok(entries) = fs::read_dir(&self.target) else {
+            return Ok(());
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let result = if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_file(&path)
+            };
+            result.map_err(|e| format!("无法清理目标目录: {} - {}", path.display(), e))?;
+        }
+        if self.target_created {
+            fs::remove_dir(&self.target).map_err(|e| format!("无法删除目标目录: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// 链接已生效后删除备份，移回完成后删除目标目录。删除失败不影响结果
+    pub fn finish(&self) -> Option<String> {
+        let (leftover, name) = if self.move_back {
+            (&self.target, "目标目录")
+        } else {
+            (&self.backup, "备份")
+        };
+        let warning = match fs::remove_dir_all(leftover) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Some(format!(
+                "{} {} 未能删除，可手动删除: {}",
+                name,
+                leftover.display(),
+                e
+            )),
+            _ => None,
+        };
+        if !self.move_back {
+            move_checkpoint::clear_for(&self.source, &self.target);
+        }
+        Self::clear();
+        warning
+    }
+
+    // 跨卷复制途中退出时，目标中已复制的文件可以在下次移动时继续使用
+    fn resumable(&self) -> bool {
+        self.stage == MoveStage::Copying
+            && !self.renamed
+            && !self.move_back
+            && move_checkpoint::exists_for(&self.source, &self.target)
+    }
+}
+
+// 按可用的链接类型依次尝试，恢复移回前的链接
+fn relink(source: &Path, target: &Path) -> Result<(), String> {
+    let mut last_error = String::new();
+    for &kind in LinkKind::available() {
+        match link::strategy_for(kind).create(source, target) {
+            Ok(()) => return Ok(()),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// 启动时检查上次是否有未完成的移动，已链接的继续完成，复制中断的保留断点，其余回滚
+pub fn recover_interrupted_move() -> Option<String> {
+    let journal = MoveJournal::load()?;
+    let message = if journal.stage == MoveStage::Linked {
+        let mut message = format!(
+            "上次{} {} 已完成",
+            journal.action(),
+            journal.source.display()
+        );
+        if let Some(warning) = journal.finish() {
+            message.push_str(&format!("，{}", warning));
+        }
+        message
+    } else if journal.resumable() {
+        MoveJournal::clear();
+        format!(
+            "上次移动 {} 在复制时中断，源文件夹保持不变。已复制的文件保留在 {}，再次移动到同一位置时会从中断处继续",
+            journal.source.display(),
+            journal.target.display()
+        )
+    } else {
+        match journal.rollback() {
+            Ok(()) => format!(
+                "上次{} {} 未完成，已恢复到{}前的状态",
+                journal.action(),
+                journal.source.display(),
+                journal.action()
+            ),
+            Err(e) => format!(
+                "上次{} {} 未完成，自动恢复失败: {}。操作记录保存在 {}",
+                journal.action(),
+                journal.source.display(),
+                e,
+                journal_path().display()
+            ),
+        }
+    };
+    logger::log_audit(&message);
+    Some(message)
+}
@@ -1,58 +1,182 @@
+use crate::link::{self, LinkKind, LinkStrategy};
 use crate::logger;
-use dirs_next as dirs;
+use crate::move_checkpoint::{self, CheckpointEntry, MoveCheckpoint};
+use crate::move_journal::{self, MoveJournal, MoveStage};
+use crate::process_check;
+use crate::protected;
+use crate::relocation;
+use crate::scanner;
+use crate::utils;
 use eframe::egui;
 use native_dialog::FileDialog;
-use std::fs;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{self, Receiver, Sender};
-use std::thread; // 如果使用的是 `dirs-next`
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+use walkdir::WalkDir;
+
+// 每次复制的块大小，同时也是进度更新和检查取消的间隔
+const COPY_BUFFER_SIZE: usize = 256 * 1024;
+
+/// 整个目录树的复制进度
+#[derive(Debug, Clone, Default)]
+pub struct MoveProgress {
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub current_path: String,
+}
+
+enum MoveMessage {
+    Stage(String),
+    Progress(MoveProgress),
+    Finished(Result<String, String>),
+}
 
 pub struct MoveModule {
     pub show_window: bool,
+    pub root: String,                   // 所在的根目录，如 Roaming
     pub folder_name: String,            // 源文件夹名（相对路径）
+    pub move_back: bool,                // 把已移动的文件夹移回原位置
     pub selected_path: Option<PathBuf>, // 目标路径
-    pub progress: f32,                  // 复制进度
+    pub progress: MoveProgress,         // 复制进度
     pub status_message: Option<String>, // 操作状态
+    pub link_kind: LinkKind,            // 在原位置创建的链接类型
+    is_busy: bool,
+    started: Option<Instant>, // 开始复制的时间，用于计算速度
+    rx: Option<Receiver<MoveMessage>>,
+    cancel_flag: Arc<AtomicBool>,
+    process_scan: Option<process_check::ProcessCheck>, // 检查是否有进程正在使用源文件夹
+    pending_protection: Option<protected::ProtectedEntry>, // 等待输入名称确认的受保护文件夹
+    protection_confirmed: Option<String>,              // 已通过输入名称确认的文件夹名
+    completed: Option<String>, // 已完成移动或移回的文件夹名，用于更新主界面列表
 }
 
 impl Default for MoveModule {
     fn default() -> Self {
         Self {
             show_window: false,
+            root: String::new(),
             folder_name: String::new(),
+            move_back: false,
             selected_path: None,
-            progress: 0.0,
+            progress: MoveProgress::default(),
             status_message: None,
+            link_kind: LinkKind::default(),
+            is_busy: false,
+            started: None,
+            rx: None,
+            cancel_flag: Arc::new(AtomicBool::new(false)),
+            process_scan: None,
+            pending_protection: None,
+            protection_confirmed: None,
+            completed: None,
         }
     }
 }
 
 impl MoveModule {
+    pub fn open(&mut self, root: &str, folder_name: &str) {
+        self.open_window(root, folder_name, false);
+    }
+
+    /// 打开移回窗口，文件夹必须是之前移动后留下的链接
+    pub fn open_move_back(&mut self, root: &str, folder_name: &str) {
+        self.open_window(root, folder_name, true);
+    }
+
+    fn open_window(&mut self, root: &str, folder_name: &str, move_back: bool) {
+        if self.is_busy {
+            return;
+        }
+        self.show_window = true;
+        self.root = root.to_string();
+        self.folder_name = folder_name.to_string();
+        self.move_back = move_back;
+        self.progress = MoveProgress::default();
+        self.status_message = None;
+    }
+
+    /// 取出已完成移动或移回的文件夹名，用于更新主界面列表
+    pub fn take_completed(&mut self) -> Option<String> {
+        self.completed.take()
+    }
+
     pub fn show_move_window(&mut self, ctx: &egui::Context) {
+        self.poll();
+        if self.is_busy {
+            ctx.request_repaint();
+        }
+
         if self.show_window {
-            egui::Window::new("移动文件夹")
+            let title = if self.move_back {
+                "移回文件夹"
+            } else {
+                "移动文件夹"
+            };
+            egui::Window::new(title)
                 .resizable(false)
                 .collapsible(false)
                 .show(ctx, |ui| {
-                    ui.label(format!("需要移动的文件夹: {}", self.folder_name));
+                    if self.move_back {
+                        ui.label(format!("需要移回的文件夹: {}", self.folder_name));
+                        if let Some(target) = scanner::link_target(&self.source_path()) {
+                            ui.label(format!("当前位置: {}", target.display()));
+                        }
+                    } else {
+                        ui.label(format!("需要移动的文件夹: {}", self.folder_name));
+                    }
 
                     // 显示目标路径选择
-                    ui.horizontal(|ui| {
-                        ui.label("目标路径:");
-                        if let Some(path) = &self.selected_path {
-                            ui.label(path.display().to_string());
-                        }
-                        if ui.button("选择目标路径").clicked() {
-                            // 使用文件对话框选择目标路径
-                            if let Ok(Some(path)) = FileDialog::new().show_open_single_dir() {
-                                self.selected_path = Some(path);
-                                println!(
-                                    "目标路径选择: {}",
-                                    self.selected_path.as_ref().unwrap().display()
-                                );
+                    if !self.move_back {
+                        ui.horizontal(|ui| {
+                            ui.label("目标路径:");
+                            if let Some(path) = &self.selected_path {
+                                ui.label(path.display().to_string());
+                            }
+                            if ui
+                                .add_enabled(!self.is_busy, egui::Button::new("选择目标路径"))
+                                .clicked()
+                            {
+                                // 使用文件对话框选择目标路径
+                                if let Ok(Some(path)) = FileDialog::new().show_open_single_dir() {
+                                    self.selected_path = Some(path);
+                                    println!(
+                                        "目标路径选择: {}",
+                                        self.selected_path.as_ref().unwrap().display()
+                                    );
+                                }
+                            }
+                        });
+                    }
+
+                    // 只有一种链接类型可用时不显示选项
+                    if !self.move_back && LinkKind::available().len() > 1 {
+                        ui.horizontal(|ui| {
+                            ui.label("链接类型:");
+                            for &kind in LinkKind::available() {
+                                if ui
+                                    .add_enabled(
+                                        !self.is_busy,
+                                        egui::RadioButton::new(
+                                            self.link_kind == kind,
+                                            kind.label(),
+                                        ),
+                                    )
+                                    .clicked()
+                                {
+                                    self.link_kind = kind;
+                                }
                             }
+                        });
+                        if self.link_kind == LinkKind::Symlink {
+                            ui.label("符号链接需要管理员权限或开启开发者模式");
                         }
-                    });
+                    }
 
                     // 显示状态信息
                     if let Some(message) = &self.status_message {
@@ -60,35 +184,148 @@ impl MoveModule {
                     }
 
                     // 显示进度条
-                    ui.add(egui::ProgressBar::new(self.progress).show_percentage());
+                    if self.is_busy {
+                        ui.label(&self.progress.current_path);
+                        ui.label(self.progress_text());
+                    }
+                    ui.add(egui::ProgressBar::new(self.progress_fraction()).show_percentage());
 
                     // 操作按钮
-                    if ui.button("确定").clicked() {
-                        if let Some(target_path) = &self.selected_path {
-                            self.start_move_folder(target_path.clone());
-                        } else {
-                            self.status_message = Some("请选择目标路径".to_string());
+                    ui.horizontal(|ui| {
+                        if self.is_busy {
+                            if ui.button("取消").clicked() {
+                                self.cancel_flag.store(true, Ordering::Relaxed);
+                            }
+                            return;
                         }
-                    }
 
-                    if ui.button("取消").clicked() {
-                        self.show_window = false;
-                    }
+                        if ui.button("确定").clicked() {
+                            if self.move_back {
+                                self.request_move_back();
+                            } else if self.selected_path.is_some() {
+                                self.request_move();
+                            } else {
+                                self.status_message = Some("请选择目标路径".to_string());
+                            }
+                        }
+
+                        if ui.button("取消").clicked() {
+                            self.show_window = false;
+                        }
+                    });
                 });
         }
+
+        // 受保护的文件夹需要输入名称确认
+        if let Some(entry) = &self.pending_protection {
+            if let Some(confirm) = protected::show_typed_confirmation(ctx, "移动", entry) {
+                self.pending_protection = None;
+                if confirm {
+                    self.protection_confirmed = Some(self.folder_name.clone());
+                    self.request_move();
+                } else {
+                    self.status_message = Some("已中止移动".to_string());
+                }
+            }
+        }
+
+        // 在后台检查占用情况，源文件夹被占用时由用户决定是否继续移动
+        if let Some(scan) = &mut self.process_scan {
+            let action = if self.move_back { "移回" } else { "移动" };
+            if let Some(proceed) = scan.show(ctx) {
+                self.process_scan = None;
+                match (proceed, self.selected_path.clone()) {
+                    (true, _) if self.move_back => self.start_move_back(),
+                    (true, Some(target_path)) => self.start_move_folder(target_path),
+                    _ => self.status_message = Some(format!("已中止{}", action)),
+                }
+            }
+        }
     }
 
-    fn start_move_folder(&mut self, target_path: PathBuf) {
-        // 获取系统 AppData 路径
-        let appdata_path = dirs::data_dir()
-            .or_else(|| dirs::config_dir()) // 备用获取 Roaming 路径
-            .unwrap_or_else(|| PathBuf::from("%appdata%"));
+    fn is_protection_confirmed(&self) -> bool {
+        self.protection_confirmed.as_ref() == Some(&self.folder_name)
+    }
+
+    // 依次检查保护级别和占用情况，都通过后才开始移动到 `selected_path`
+    fn request_move(&mut self) {
+        let source_path = self.source_path();
+
+        if !self.is_protection_confirmed() {
+            match protected::check_path(&source_path) {
+                Some(entry) if entry.severity == protected::Severity::Blocked => {
+                    self.status_message = Some(format!(
+                        "文件夹 {} 受保护（{}），禁止移动",
+                        self.folder_name, entry.reason
+                    ));
+                    return;
+                }
+                Some(entry) => {
+                    self.pending_protection = Some(entry);
+                    return;
+                }
+                None => {}
+            }
+        }
+
+        self.process_scan = Some(process_check::ProcessCheck::start(source_path, "移动"));
+    }
+
+    // 移回前只需要检查占用情况，受保护的文件夹也可以移回
+    fn request_move_back(&mut self) {
+        self.process_scan = Some(process_check::ProcessCheck::start(
+            self.source_path(),
+            "移回",
+        ));
+    }
+
+    fn source_path(&self) -> PathBuf {
+        // 获取当前根目录对应的 AppData 路径
+        let appdata_path =
+            utils::get_appdata_dir(&self.root).unwrap_or_else(|| PathBuf::from("%appdata%"));
 
-        let source_path = appdata_path.join(&self.folder_name);
+        appdata_path.join(&self.folder_name)
+    }
+
+    // 准备进度和取消标志，返回交给后台线程的发送端
+    fn begin_task(&mut self, status: &str) -> (Sender<MoveMessage>, Arc<AtomicBool>) {
+        let (tx, rx) = mpsc::channel();
+        self.rx = Some(rx);
+        self.is_busy = true;
+        self.started = Some(Instant::now());
+        self.progress = MoveProgress::default();
+        self.status_message = Some(status.to_string());
+        self.cancel_flag = Arc::new(AtomicBool::new(false));
+        (tx, Arc::clone(&self.cancel_flag))
+    }
+
+    fn start_move_back(&mut self) {
+        let source_path = self.source_path();
+        let (tx, cancel_flag) = self.begin_task("正在移回文件夹...");
+
+        thread::spawn(move || {
+            let result = move_back_folder(&source_path, &cancel_flag, &tx);
+            if result.is_ok() {
+                relocation::forget(&source_path);
+            }
+            let _ = tx.send(MoveMessage::Finished(result));
+        });
+    }
+
+    fn start_move_folder(&mut self, target_path: PathBuf) {
+        let source_path = self.source_path();
 
         // 调试日志打印完整路径
         println!("完整源文件夹路径: {}", source_path.display());
 
+        if let Err(err) =
+            protected::ensure_allowed(&source_path, "移动", self.is_protection_confirmed())
+        {
+            logger::log_error(&err);
+            self.status_message = Some(err);
+            return;
+        }
+
         // 验证源文件夹是否存在
         if !source_path.exists() {
             self.status_message = Some(format!("源文件夹不存在: {}", source_path.display()));
@@ -97,137 +334,1244 @@ impl MoveModule {
             return;
         }
 
-        let (tx, rx): (
-            Sender<Result<String, String>>,
-            Receiver<Result<String, String>>,
-        ) = mpsc::channel();
-        self.progress = 0.0;
-        self.status_message = Some("正在移动文件夹...".to_string());
+        let (tx, cancel_flag) = self.begin_task("正在移动文件夹...");
+        let link_kind = self.link_kind;
 
-        // 启动后台线程执行移动逻辑
+        // 启动后台线程执行移动逻辑，结果由 poll() 在界面线程中接收
         thread::spawn(move || {
-            println!(
-                "开始复制: 从 {} 到 {}",
-                source_path.display(),
-                target_path.display()
+            let strategy = link::strategy_for(link_kind);
+            let result = move_folder(
+                &source_path,
+                &target_path,
+                strategy.as_ref(),
+                &cancel_flag,
+                &tx,
             );
-
-            if let Err(err) = fs::create_dir_all(&target_path) {
-                let _ = tx.send(Err(format!("无法创建目标目录: {}", err)));
-                return;
+            if result.is_ok() {
+                let size = scanner::calculate_folder_size(&target_path);
+                relocation::record_move(&source_path, &target_path, link_kind, size);
             }
+            let _ = tx.send(MoveMessage::Finished(result));
+        });
+    }
 
-            if let Err(err) = copy_dir_with_progress(&source_path, &target_path, &tx) {
-                let _ = tx.send(Err(format!("复制失败: {}", err)));
-                return;
+    // 接收后台线程的进度和结果，不阻塞界面
+    fn poll(&mut self) {
+        let Some(rx) = &self.rx else {
+            return;
+        };
+        while let Ok(msg) = rx.try_recv() {
+            match msg {
+                MoveMessage::Stage(stage) => self.progress.current_path = stage,
+                MoveMessage::Progress(progress) => self.progress = progress,
+                MoveMessage::Finished(result) => {
+                    self.is_busy = false;
+                    match result {
+                        Ok(status) => {
+                            logger::log_info(&status);
+                            self.progress.bytes_done = self.progress.bytes_total;
+                            self.status_message = Some(status);
+                            self.completed = Some(self.folder_name.clone());
+                        }
+                        Err(err) => {
+                            logger::log_error(&err);
+                            self.status_message = Some(err);
+                        }
+                    }
+                }
             }
+        }
+    }
 
-            // 删除原文件夹
-            if let Err(err) = fs::remove_dir_all(&source_path) {
-                let _ = tx.send(Err(format!("删除源目录失败: {}", err)));
-                return;
-            }
+    fn progress_fraction(&self) -> f32 {
+        if self.progress.bytes_total == 0 {
+            0.0
+        } else {
+            self.progress.bytes_done as f32 / self.progress.bytes_total as f32
+        }
+    }
 
-            // 创建符号链接
-            if cfg!(target_os = "windows") {
-                let output = std::process::Command::new("cmd")
-                    .args([
-                        "/C",
-                        "mklink",
-                        "/D",
-                        &format!("\"{}\"", source_path.display()),
-                        &format!("\"{}\"", target_path.display()),
-                    ])
-                    .output();
-
-                match output {
-                    Ok(output) if output.status.success() => {
-                        let _ = tx.send(Ok(format!(
-                            "创建符号链接成功: {} -> {}",
-                            source_path.display(),
-                            target_path.display()
-                        )));
-                    }
-                    Ok(output) => {
-                        let _ = tx.send(Err(format!(
-                            "创建符号链接失败: {}",
-                            String::from_utf8_lossy(&output.stderr)
-                        )));
-                    }
-                    Err(err) => {
-                        let _ = tx.send(Err(format!("符号链接命令执行失败: {}", err)));
-                    }
-                }
-            } //else {
-              // 非 Windows 系统，尝试创建软链接
-              //if let Err(err) = std::os::unix::fs::symlink(&target_path, &source_path) {
-              //    let _ = tx.send(Err(format!("创建符号链接失败: {}", err)));
-              //} else {
-              //    let _ = tx.send(Ok(format!(
-              //        "创建符号链接成功: {} -> {}",
-              //        source_path.display(),
-              //        target_path.display()
-              //    )));
-              //}
-              //}
-        });
+    // 已复制大小、速度和预计剩余时间
+    fn progress_text(&self) -> String {
+        let mut text = format!(
+            "已复制 {} / {}",
+            utils::format_size(self.progress.bytes_done),
+            utils::format_size(self.progress.bytes_total)
+        );
+        let elapsed = self
+            .started
+            .map(|t| t.elapsed().as_secs_f64())
+            .unwrap_or(0.0);
+        if elapsed > 0.0 && self.progress.bytes_done > 0 {
+            let speed = self.progress.bytes_done as f64 / elapsed;
+            let remaining = self
+                .progress
+                .bytes_total
+                .saturating_sub(self.progress.bytes_done);
+            text.push_str(&format!(
+                "，速度 {}/s，预计剩余 {}",
+                utils::format_size(speed as u64),
+                format_eta((remaining as f64 / speed) as u64)
+            ));
+        }
+        text
+    }
+}
 
-        // 主线程接收消息并更新状态
-        for msg in rx {
-            match msg {
-                Ok(status) => {
-                    self.status_message = Some(status);
-                    self.progress = 1.0;
-                    break;
-                }
-                Err(err) => {
-                    self.status_message = Some(err);
-                    break;
-                }
+fn format_eta(seconds: u64) -> String {
+    if seconds >= 3600 {
+        format!("{} 小时 {} 分", seconds / 3600, seconds % 3600 / 60)
+    } else if seconds >= 60 {
+        format!("{} 分 {} 秒", seconds / 60, seconds % 60)
+    } else {
+        format!("{} 秒", seconds)
+    }
+}
+
+// 在后台线程中执行整个移动事务，任何一步失败都会回滚。
+// 同一个卷上直接重命名，跨卷时复制
+fn move_folder(
+    source_path: &Path,
+    target_path: &Path,
+    strategy: &dyn LinkStrategy,
+    cancel_flag: &AtomicBool,
+    tx: &Sender<MoveMessage>,
+) -> Result<String, String> {
+    let renamed = utils::same_volume(source_path, target_path);
+    move_folder_with(source_path, target_path, renamed, strategy, cancel_flag, tx)
+}
+
+fn move_folder_with(
+    source_path: &Path,
+    target_path: &Path,
+    renamed: bool,
+    strategy: &dyn LinkStrategy,
+    cancel_flag: &AtomicBool,
+    tx: &Sender<MoveMessage>,
+) -> Result<String, String> {
+    if let Some(journal) = MoveJournal::load() {
+        return Err(format!(
+            "上次移动 {} 尚未恢复，请先检查 {}",
+            journal.source.display(),
+            move_journal::journal_path().display()
+        ));
+    }
+
+    // 链接会指向整个目标目录，目标中不能混入其他内容，回滚时也才能安全清空。
+    // 上次复制中断留下的内容除外，这次会从断点继续
+    let resuming = move_checkpoint::exists_for(source_path, target_path);
+    let target_created = !target_path.exists();
+    if !target_created
+        && !resuming
+        && fs::read_dir(target_path)
+            .map_err(|err| format!("无法读取目标目录: {}", err))?
+            .next()
+            .is_some()
+    {
+        return Err("目标目录不为空，请选择一个空目录".to_string());
+    }
+    let backup = move_journal::backup_path(source_path);
+    if backup.exists() {
+        return Err(format!("备份路径已存在，请先处理: {}", backup.display()));
+    }
+
+    // 跨卷复制前先确认目标磁盘放得下
+    if !renamed {
+        check_target_space(source_path, target_path)?;
+    }
+
+    let mut journal = MoveJournal::new(source_path, target_path, target_created, renamed);
+    journal.save()?;
+    if let Err(err) = fs::create_dir_all(target_path) {
+        MoveJournal::clear();
+        return Err(format!("无法创建目标目录: {}", err));
+    }
+
+    let result = if renamed {
+        run_rename_stages(&mut journal, strategy, cancel_flag, tx)
+    } else {
+        run_move_stages(&mut journal, strategy, cancel_flag, tx)
+    };
+    if let Err(err) = result {
+        // 复制中途出错（如休眠、拔出移动硬盘）时保留已复制的文件，用户取消时才清空。
+        // 重命名失败后改为复制时 journal.renamed 也会变为 false
+        if journal.stage == MoveStage::Copying
+            && !journal.renamed
+            && !cancel_flag.load(Ordering::Relaxed)
+        {
+            MoveJournal::clear();
+            return Err(format!(
+                "{}。源文件夹保持不变，已复制的文件保留在 {}，再次移动到同一位置时会从中断处继续",
+                err,
+                target_path.display()
+            ));
+        }
+        return match journal.rollback() {
+            Ok(()) => Err(format!("{}，已回滚，源文件夹保持不变", err)),
+            Err(rollback_err) => Err(format!(
+                "{}，回滚失败: {}。操作记录保存在 {}，下次启动时会再次尝试恢复",
+                err,
+                rollback_err,
+                move_journal::journal_path().display()
+            )),
+        };
+    }
+
+    logger::log_audit(&format!(
+        "已移动 {} 到 {}",
+        source_path.display(),
+        target_path.display()
+    ));
+    let mut message = format!(
+        "创建{}成功: {} -> {}",
+        strategy.kind().label(),
+        source_path.display(),
+        target_path.display()
+    );
+    if let Some(warning) = journal.finish() {
+        message.push_str(&format!("，{}", warning));
+    }
+    Ok(message)
+}
+
+/// 把已移动的文件夹移回原位置：数据复制或重命名回原路径，删除链接并清理目标目录。
+/// 与移动使用同样的日志、校验和回滚
+fn move_back_folder(
+    source_path: &Path,
+    cancel_flag: &AtomicBool,
+    tx: &Sender<MoveMessage>,
+) -> Result<String, String> {
+    if !move_journal::is_link(source_path) {
+        return Err(format!("{} 不是链接，无需移回", source_path.display()));
+    }
+    let target_path = relocation::move_back_target(&relocation::load_relocations(), source_path)?;
+    let renamed = utils::same_volume(&target_path, source_path);
+    move_back_with(source_path, &target_path, renamed, cancel_flag, tx)
+}
+
+fn move_back_with(
+    source_path: &Path,
+    target_path: &Path,
+    renamed: bool,
+    cancel_flag: &AtomicBool,
+    tx: &Sender<MoveMessage>,
+) -> Result<String, String> {
+    if let Some(journal) = MoveJournal::load() {
+        return Err(format!(
+            "上次移动 {} 尚未恢复，请先检查 {}",
+            journal.source.display(),
+            move_journal::journal_path().display()
+        ));
+    }
+    let staging = move_journal::backup_path(source_path);
+    if staging.exists() {
+        return Err(format!("临时路径已存在，请先处理: {}", staging.display()));
+    }
+    if !renamed {
+        let needed = scanner::calculate_folder_size(target_path);
+        let parent = source_path.parent().unwrap_or(source_path);
+        if let Some(free) = utils::available_space(parent) {
+            if free < needed {
+                return Err(format!(
+                    "原位置磁盘空间不足: 需要 {}，可用 {}",
+                    utils::format_size(needed),
+                    utils::format_size(free)
+                ));
             }
         }
     }
+
+    let mut journal = MoveJournal::new_move_back(source_path, target_path, renamed);
+    journal.save()?;
+    let result = if renamed {
+        run_rename_back_stages(&mut journal, tx)
+    } else {
+        run_move_back_stages(&mut journal, cancel_flag, tx)
+    };
+    if let Err(err) = result {
+        return match journal.rollback() {
+            Ok(()) => Err(format!("{}，已回滚，链接保持不变", err)),
+            Err(rollback_err) => Err(format!(
+                "{}，回滚失败: {}。操作记录保存在 {}，下次启动时会再次尝试恢复",
+                err,
+                rollback_err,
+                move_journal::journal_path().display()
+            )),
+        };
+    }
+
+    logger::log_audit(&format!(
+        "已把 {} 移回 {}",
+        target_path.display(),
+        source_path.display()
+    ));
+    let mut message = format!(
+        "已移回: {} -> {}",
+        target_path.display(),
+        source_path.display()
+    );
+    if let Some(warning) = journal.finish() {
+        message.push_str(&format!("，{}", warning));
+    }
+    Ok(message)
+}
+
+// 复制到原位置旁的临时目录 → 校验 → 删除链接 → 临时目录改为原名。
+// 目标目录中的数据在 finish() 之前保持不变
+fn run_move_back_stages(
+    journal: &mut MoveJournal,
+    cancel_flag: &AtomicBool,
+    tx: &Sender<MoveMessage>,
+) -> Result<(), String> {
+    let source_path = journal.source.clone();
+    let target_path = journal.target.clone();
+    let staging = journal.backup.clone();
+
+    fs::create_dir(&staging).map_err(|err| format!("无法创建临时目录: {}", err))?;
+    copy_dir_with_progress(&target_path, &staging, None, cancel_flag, tx)
+        .map_err(|err| format!("复制失败: {}", err))?;
+
+    journal.set_stage(MoveStage::Verifying)?;
+    send_stage(tx, "正在校验复制结果");
+    verify_copy(&target_path, &staging, cancel_flag)?;
+
+    journal.set_stage(MoveStage::SourceRenamed)?;
+    send_stage(tx, "正在删除链接");
+    move_journal::remove_link(&source_path).map_err(|err| format!("无法删除链接: {}", err))?;
+    fs::rename(&staging, &source_path).map_err(|err| format!("无法放回原位置: {}", err))?;
+
+    journal.set_stage(MoveStage::Linked)
+}
+
+// 同一个卷上删除链接后直接把目标目录重命名回原位置
+fn run_rename_back_stages(
+    journal: &mut MoveJournal,
+    tx: &Sender<MoveMessage>,
+) -> Result<(), String> {
+    journal.set_stage(MoveStage::SourceRenamed)?;
+    send_stage(tx, "正在删除链接");
+    move_journal::remove_link(&journal.source).map_err(|err| format!("无法删除链接: {}", err))?;
+    fs::rename(&journal.target, &journal.source)
+        .map_err(|err| format!("无法放回原位置: {}", err))?;
+
+    journal.set_stage(MoveStage::Linked)
+}
+
+// 复制 → 校验 → 源文件夹改名为备份 → 创建链接 → 检查链接。
+// 每一步开始前先更新日志，保证中途退出时能知道需要撤销什么
+fn run_move_stages(
+    journal: &mut MoveJournal,
+    strategy: &dyn LinkStrategy,
+    cancel_flag: &AtomicBool,
+    tx: &Sender<MoveMessage>,
+) -> Result<(), String> {
+    let source_path = journal.source.clone();
+    let target_path = journal.target.clone();
+
+    let mut checkpoint = MoveCheckpoint::open(&source_path, &target_path)?;
+    copy_dir_with_progress(
+        &source_path,
+        &target_path,
+        Some(&mut checkpoint),
+        cancel_flag,
+        tx,
+    )
+    .map_err(|err| format!("复制失败: {}", err))?;
+
+    journal.set_stage(MoveStage::Verifying)?;
+    send_stage(tx, "正在校验复制结果");
+    verify_copy(&source_path, &target_path, cancel_flag)?;
+
+    journal.set_stage(MoveStage::SourceRenamed)?;
+    send_stage(tx, "正在创建链接");
+    fs::rename(&source_path, &journal.backup)
+        .map_err(|err| format!("无法重命名源文件夹: {}", err))?;
+    strategy.create(&source_path, &target_path)?;
+    check_link(&source_path, &target_path)?;
+
+    journal.set_stage(MoveStage::Linked)
+}
+
+// 同一个卷上的快速路径：源文件夹整体重命名到目标位置 → 创建链接 → 检查链接。
+// 重命名是原子操作，不需要复制和校验。
+// 卷检测不准确（如绑定挂载）导致无法跨设备重命名时，改为复制
+fn run_rename_stages(
+    journal: &mut MoveJournal,
+    strategy: &dyn LinkStrategy,
+    cancel_flag: &AtomicBool,
+    tx: &Sender<MoveMessage>,
+) -> Result<(), String> {
+    let source_path = journal.source.clone();
+    let target_path = journal.target.clone();
+
+    journal.set_stage(MoveStage::SourceRenamed)?;
+    send_stage(tx, "正在重命名到目标位置");
+    // 目标是之前确认过的空目录，Windows 上不能重命名到已存在的目录
+    fs::remove_dir(&target_path).map_err(|err| format!("无法替换目标目录: {}", err))?;
+    match fs::rename(&source_path, &target_path) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+            logger::log_info(&format!(
+                "无法跨设备重命名 {}，改为复制",
+                source_path.display()
+            ));
+            fs::create_dir(&target_path).map_err(|err| format!("无法创建目标目录: {}", err))?;
+            journal.renamed = false;
+            journal.set_stage(MoveStage::Copying)?;
+            check_target_space(&source_path, &target_path)?;
+            return run_move_stages(journal, strategy, cancel_flag, tx);
+        }
+        Err(err) => return Err(format!("无法重命名源文件夹: {}", err)),
+    }
+
+    send_stage(tx, "正在创建链接");
+    strategy.create(&source_path, &target_path)?;
+    check_link(&source_path, &target_path)?;
+
+    journal.set_stage(MoveStage::Linked)
 }
 
-// 带进度回调的目录复制函数
+// 确认目标磁盘放得下源文件夹，断点续传时扣除已复制的部分
+fn check_target_space(source_path: &Path, target_path: &Path) -> Result<(), String> {
+    let mut needed = scanner::calculate_folder_size(source_path);
+    if move_checkpoint::exists_for(source_path, target_path) {
+        if let Ok(checkpoint) = MoveCheckpoint::open(source_path, target_path) {
+            needed = needed.saturating_sub(checkpoint.copied_bytes());
+        }
+    }
+    if let Some(free) = utils::available_space(target_path) {
+        if free < needed {
+            return Err(format!(
+                "目标磁盘空间不足: 需要 {}，可用 {}",
+                utils::format_size(needed),
+                utils::format_size(free)
+            ));
+        }
+    }
+    Ok(())
+}
+
+// 确认应用访问原路径时确实会到达新位置
+fn check_link(source_path: &Path, target_path: &Path) -> Result<(), String> {
+    let resolved = fs::canonicalize(source_path).ok();
+    if !move_journal::is_link(source_path)
+        || resolved.is_none()
+        || resolved != fs::canonicalize(target_path).ok()
+    {
+        return Err(format!("链接未指向目标目录: {}", source_path.display()));
+    }
+    Ok(())
+}
+
+fn send_stage(tx: &Sender<MoveMessage>, stage: &str) {
+    let _ = tx.send(MoveMessage::Stage(stage.to_string()));
+}
+
+// 校验失败时最多列出的文件数
+const MISMATCH_REPORT_LIMIT: usize = 5;
+
+// 按相对路径、大小和 SHA-256 比较整个目录树
+fn verify_copy(source: &Path, target: &Path, cancel_flag: &AtomicBool) -> Result<(), String> {
+    let mismatches = utils::compare_dirs_hash(source, target, cancel_flag)
+        .map_err(|err| format!("校验失败: {}", err))?;
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+
+    for mismatch in &mismatches {
+        logger::log_error(&format!("校验不一致: {}", mismatch));
+    }
+    let mut message = format!(
+        "校验失败，{} 个文件不一致: {}",
+        mismatches.len(),
+        mismatches
+            .iter()
+            .take(MISMATCH_REPORT_LIMIT)
+            .map(|m| m.to_string())
+            .collect::<Vec<_>>()
+            .join("、")
+    );
+    if mismatches.len() > MISMATCH_REPORT_LIMIT {
+        message.push_str(" ……");
+    }
+    Err(message)
+}
+
+// 复制整个目录树，按字节汇报进度。失败时由调用方回滚。
+// 保留时间戳、权限、目录树内的符号链接和 Unix 扩展属性；
+// Windows 的 ACL 不复制，新文件继承目标目录的权限。
+// 传入断点时跳过上次已复制且未变化的文件，并记录本次复制完成的文件
 fn copy_dir_with_progress(
     source: &Path,
     target: &Path,
-    tx: &Sender<Result<String, String>>,
+    checkpoint: Option<&mut MoveCheckpoint>,
+    cancel_flag: &AtomicBool,
+    tx: &Sender<MoveMessage>,
 ) -> Result<(), String> {
-    let entries: Vec<_> = fs::read_dir(source)
-        .map_err(|err| format!("无法读取目录: {}", err))?
-        .collect();
+    // 先统计整棵树的大小，进度才能跨目录连续
+    let mut entries = Vec::new();
+    let mut progress = MoveProgress::default();
+    for entry in WalkDir::new(source).min_depth(1) {
+        let entry = entry.map_err(|err| format!("无法读取条目: {}", err))?;
+        if entry.file_type().is_file() {
+            progress.bytes_total += entry.metadata().map(|m| m.len()).unwrap_or(0);
+        }
+        entries.push(entry);
+    }
 
-    let total_entries = entries.len() as f32;
-    let mut copied_entries = 0.0;
+    copy_entries(
+        source,
+        target,
+        &entries,
+        &mut progress,
+        checkpoint,
+        cancel_flag,
+        tx,
+    )
+}
 
-    for entry in entries {
-        let entry = entry.map_err(|err| format!("无法读取条目: {}", err))?;
-        let file_type = entry
-            .file_type()
-            .map_err(|err| format!("无法获取文件类型: {}", err))?;
+fn copy_entries(
+    source: &Path,
+    target: &Path,
+    entries: &[walkdir::DirEntry],
+    progress: &mut MoveProgress,
+    mut checkpoint: Option<&mut MoveCheckpoint>,
+    cancel_flag: &AtomicBool,
+    tx: &Sender<MoveMessage>,
+) -> Result<(), String> {
+    let mut buffer = vec![0u8; COPY_BUFFER_SIZE];
+    let mut directories = Vec::new();
 
+    for entry in entries {
         let src_path = entry.path();
-        let dest_path = target.join(entry.file_name());
+        let relative = src_path.strip_prefix(source).unwrap_or(src_path);
+        let dest_path = target.join(relative);
+
+        // 读取内容之前取元数据，避免访问时间被复制过程改掉
+        let metadata = entry
+            .metadata()
+            .map_err(|err| format!("无法读取条目: {}", err))?;
+
+        // 继续上次的复制时，目标中可能已有同名的链接或文件
+        let checkpointing = checkpoint.is_some();
+        if checkpointing && !entry.file_type().is_dir() {
+            if let Some(done) = checkpoint.as_ref().and_then(|c| c.get(relative)) {
+                if done.matches(&metadata) && copied_file_intact(&dest_path, done) {
+                    progress.bytes_done += done.size;
+                    let _ = tx.send(MoveMessage::Progress(progress.clone()));
+                    continue;
+                }
+            }
+            let _ = fs::remove_file(&dest_path);
+        }
+
+        if entry.file_type().is_symlink() {
+            copy_symlink(src_path, &dest_path)?;
+            let _ = filetime::set_symlink_file_times(
+                &dest_path,
+                filetime::FileTime::from_last_access_time(&metadata),
+                filetime::FileTime::from_last_modification_time(&metadata),
+            );
+            continue;
+        }
+
+        if entry.file_type().is_dir() {
+            if !dest_path.exists() {
+                fs::create_dir(&dest_path).map_err(|err| format!("无法创建目录: {}", err))?;
+            }
+            // 目录的修改时间会随着写入子项改变，全部复制完再设置
+            directories.push((dest_path, metadata));
+            continue;
+        }
+
+        if !entry.file_type().is_file() {
+            // 管道、套接字等特殊文件不能按内容复制，只重建节点
+            copy_special(src_path, &dest_path, &metadata)?;
+            copy_metadata(src_path, &dest_path, &metadata);
+            continue;
+        }
+
+        progress.current_path = relative.display().to_string();
+        let mut input = File::open(src_path).map_err(|err| format!("无法读取文件: {}", err))?;
+        let mut output =
+            File::create(&dest_path).map_err(|err| format!("无法复制文件: {}", err))?;
+        let mut hasher = checkpointing.then(Sha256::new);
+
+        loop {
+            if cancel_flag.load(Ordering::Relaxed) {
+                return Err("已取消移动".to_string());
+            }
+            let read = input
+                .read(&mut buffer)
+                .map_err(|err| format!("无法读取文件: {}", err))?;
+            if read == 0 {
+                break;
+            }
+            output
+                .write_all(&buffer[..read])
+                .map_err(|err| format!("无法复制文件: {}", err))?;
+            if let Some(hasher) = &mut hasher {
+                hasher.update(&buffer[..read]);
+            }
+            progress.bytes_done += read as u64;
+            let _ = tx.send(MoveMessage::Progress(progress.clone()));
+        }
+        drop(output);
+
+        copy_metadata(src_path, &dest_path, &metadata);
+        if let (Some(checkpoint), Some(hasher)) = (checkpoint.as_deref_mut(), hasher) {
+            let sha256 = format!("{:x}", hasher.finalize());
+            checkpoint.record(relative, CheckpointEntry::new(&metadata, sha256))?;
+        }
+    }
+
+    // 从最深的目录开始，最后是目标目录本身
+    if let Ok(metadata) = fs::metadata(source) {
+        directories.insert(0, (target.to_path_buf(), metadata));
+    }
+    for (dest_path, metadata) in directories.iter().rev() {
+        let src_path = source.join(dest_path.strip_prefix(target).unwrap_or(dest_path));
+        copy_metadata(&src_path, dest_path, metadata);
+    }
+
+    Ok(())
+}
 
-        println!(
-            "复制文件: 从 {} 到 {}",
+// 上次复制完成的文件仍然完整，大小和 SHA-256 都与断点记录一致
+fn copied_file_intact(dest_path: &Path, done: &CheckpointEntry) -> bool {
+    fs::metadata(dest_path)
+        .map(|m| m.is_file() && m.len() == done.size)
+        .unwrap_or(false)
+        && utils::hash_file(dest_path)
+            .map(|hash| hash == done.sha256)
+            .unwrap_or(false)
+}
+
+// 按原样重建链接，相对路径的链接仍然是相对的
+fn copy_symlink(src_path: &Path, dest_path: &Path) -> Result<(), String> {
+    let link_target = fs::read_link(src_path).map_err(|err| format!("无法读取链接: {}", err))?;
+
+    #[cfg(unix)]
+    let result = std::os::unix::fs::symlink(&link_target, dest_path);
+    #[cfg(windows)]
+    let result = if fs::metadata(src_path).map(|m| m.is_dir()).unwrap_or(false) {
+        std::os::windows::fs::symlink_dir(&link_target, dest_path)
+    } else {
+        std::os::windows::fs::symlink_file(&link_target, dest_path)
+    };
+    #[cfg(not(any(unix, windows)))]
+    let result: std::io::Result<()> = Err(std::io::Error::from(std::io::ErrorKind::Unsupported));
+
+    result.map_err(|err| format!("无法复制链接 {}: {}", src_path.display(), err))
+}
+
+#[cfg(unix)]
+fn copy_special(src_path: &Path, dest_path: &Path, metadata: &fs::Metadata) -> Result<(), String> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    let path = std::ffi::CString::new(dest_path.as_os_str().as_bytes())
+        .map_err(|err| format!("无法复制特殊文件: {}", err))?;
+    let mode = metadata.mode() as libc::mode_t;
+    let result = unsafe {
+        if metadata.file_type().is_fifo() {
+            libc::mkfifo(path.as_ptr(), mode & 0o7777)
+        } else {
+            libc::mknod(path.as_ptr(), mode, metadata.rdev() as libc::dev_t)
+        }
+    };
+    if result != 0 {
+        return Err(format!(
+            "无法复制特殊文件 {}: {}",
             src_path.display(),
-            dest_path.display()
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn copy_special(
+    src_path: &Path,
+    _dest_path: &Path,
+    _metadata: &fs::Metadata,
+) -> Result<(), String> {
+    Err(format!("不支持复制特殊文件: {}", src_path.display()))
+}
+
+// 复制扩展属性、权限和时间戳。与 fs::copy 一致，失败时不中断移动。
+// 权限放在扩展属性之后，否则只读文件无法写入属性
+fn copy_metadata(src_path: &Path, dest_path: &Path, metadata: &fs::Metadata) {
+    copy_xattrs(src_path, dest_path);
+    let _ = fs::set_permissions(dest_path, metadata.permissions());
+    // set_file_times 会打开文件，遇到管道会阻塞；按路径设置时间不需要打开
+    let _ = filetime::set_symlink_file_times(
+        dest_path,
+        filetime::FileTime::from_last_access_time(metadata),
+        filetime::FileTime::from_last_modification_time(metadata),
+    );
+}
+
+#[cfg(unix)]
+fn copy_xattrs(src_path: &Path, dest_path: &Path) {
+    let Ok(names) = xattr::list(src_path) else {
+        return;
+    };
+    for name in names {
+        if let Ok(Some(value)) = xattr::get(src_path, &name) {
+            let _ = xattr::set(dest_path, &name, &value);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn copy_xattrs(_src_path: &Path, _dest_path: &Path) {}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("adc_move_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn make_source(base: &Path) -> PathBuf {
+        let source = base.join("App");
+        fs::create_dir_all(source.join("Cache/nested")).unwrap();
+        fs::write(source.join("settings.json"), b"{}").unwrap();
+        fs::write(source.join("Cache/nested/data.bin"), vec![7u8; 300_000]).unwrap();
+        source
+    }
+
+    fn run_move(source: &Path, target: &Path) -> Result<String, String> {
+        let (tx, _rx) = mpsc::channel();
+        move_folder(
+            source,
+            target,
+            &link::SymlinkStrategy,
+            &AtomicBool::new(false),
+            &tx,
+        )
+    }
+
+    #[test]
+    fn move_replaces_source_with_symlink() {
+        let base = temp_dir("symlink");
+        let source = make_source(&base);
+        let target = base.join("moved");
+
+        run_move(&source, &target).unwrap();
+
+        assert!(move_journal::is_link(&source));
+        assert_eq!(
+            fs::canonicalize(&source).unwrap(),
+            fs::canonicalize(&target).unwrap()
+        );
+        assert_eq!(
+            fs::read(source.join("Cache/nested/data.bin")).unwrap(),
+            vec![7u8; 300_000]
         );
+        assert!(!move_journal::backup_path(&source).exists());
+        assert!(MoveJournal::load().is_none());
 
-        if file_type.is_dir() {
-            fs::create_dir_all(&dest_path).map_err(|err| format!("无法创建目录: {}", err))?;
-            copy_dir_with_progress(&src_path, &dest_path, tx)?;
-        } else {
-            fs::copy(&src_path, &dest_path).map_err(|err| format!("无法复制文件: {}", err))?;
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn symlink_to_relative_target_resolves_from_source() {
+        let base = temp_dir("relative");
+        let source = make_source(&base);
+
+        // 相对于当前工作目录、指向临时目录的目标路径，链接所在目录与之不同
+        let up: PathBuf = std::env::current_dir()
+            .unwrap()
+            .components()
+            .skip(1)
+            .map(|_| "..")
+            .collect();
+        let relative_target = up.join(base.strip_prefix("/").unwrap()).join("moved");
+        assert!(relative_target.is_relative());
+
+        run_move(&source, &relative_target).unwrap();
+
+        let link = fs::read_link(&source).unwrap();
+        assert!(link.is_absolute());
+        assert_eq!(
+            fs::canonicalize(&source).unwrap(),
+            fs::canonicalize(&relative_target).unwrap()
+        );
+        assert!(source.join("settings.json").exists());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn non_empty_target_is_rejected_without_changes() {
+        let base = temp_dir("non_empty");
+        let source = make_source(&base);
+        let target = base.join("occupied");
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("other.txt"), b"keep").unwrap();
+
+        assert!(run_move(&source, &target).is_err());
+        assert!(!move_journal::is_link(&source));
+        assert!(source.join("settings.json").exists());
+        assert!(target.join("other.txt").exists());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    struct FailingLink;
+
+    impl LinkStrategy for FailingLink {
+        fn kind(&self) -> LinkKind {
+            LinkKind::Symlink
         }
 
-        copied_entries += 1.0;
-        let progress = copied_entries / total_entries;
-        let _ = tx.send(Ok(format!("复制进度: {:.2}%", progress * 100.0)));
+        fn create(&self, _link: &Path, _target: &Path) -> Result<(), String> {
+            Err("模拟创建链接失败".to_string())
+        }
     }
 
-    Ok(())
+    #[test]
+    fn link_failure_rolls_back_to_original_state() {
+        let base = temp_dir("link_failure");
+        let source = make_source(&base);
+        let target = base.join("moved");
+        let (tx, _rx) = mpsc::channel();
+
+        let err =
+            move_folder(&source, &target, &FailingLink, &AtomicBool::new(false), &tx).unwrap_err();
+
+        assert!(err.contains("模拟创建链接失败"));
+        assert!(!move_journal::is_link(&source));
+        assert!(source.join("Cache/nested/data.bin").exists());
+        assert!(!target.exists());
+        assert!(!move_journal::backup_path(&source).exists());
+        assert!(MoveJournal::load().is_none());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    // 同一个卷上也强制走复制路径，在校验和创建链接两个阶段模拟失败
+    #[test]
+    fn copy_path_failures_roll_back_to_original_state() {
+        let base = temp_dir("copy_failure");
+        let source = make_source(&base);
+        let target = base.join("moved");
+        let (tx, _rx) = mpsc::channel();
+        let assert_rolled_back = |err: &str| {
+            assert!(err.contains("已回滚"), "{}", err);
+            assert!(!move_journal::is_link(&source));
+            assert_eq!(fs::read(source.join("settings.json")).unwrap(), b"{}");
+            assert!(!target.exists());
+            assert!(!move_journal::backup_path(&source).exists());
+            assert!(!move_checkpoint::exists_for(&source, &target));
+            assert!(MoveJournal::load().is_none());
+        };
+
+        // SourceRenamed：源文件夹已改名为备份，创建链接失败
+        let err = move_folder_with(
+            &source,
+            &target,
+            false,
+            &FailingLink,
+            &AtomicBool::new(false),
+            &tx,
+        )
+        .unwrap_err();
+        assert!(err.contains("模拟创建链接失败"), "{}", err);
+        assert_rolled_back(&err);
+
+        // Verifying：断点记录了一个内容已损坏的文件，复制时跳过它，校验时发现不一致
+        fs::create_dir(&target).unwrap();
+        fs::write(target.join("settings.json"), b"XX").unwrap();
+        let source_meta = fs::metadata(source.join("settings.json")).unwrap();
+        let corrupted = utils::hash_file(&target.join("settings.json")).unwrap();
+        MoveCheckpoint::open(&source, &target)
+            .unwrap()
+            .record(
+                Path::new("settings.json"),
+                CheckpointEntry::new(&source_meta, corrupted),
+            )
+            .unwrap();
+
+        let err = move_folder_with(
+            &source,
+            &target,
+            false,
+            &link::SymlinkStrategy,
+            &AtomicBool::new(false),
+            &tx,
+        )
+        .unwrap_err();
+        assert!(err.contains("settings.json"), "{}", err);
+        // 目标目录不是本次创建的，回滚时只清空内容
+        assert!(fs::read_dir(&target).unwrap().next().is_none());
+        fs::remove_dir(&target).unwrap();
+        assert_rolled_back(&err);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    // 误判为同一个卷时，重命名失败后改为复制
+    #[test]
+    fn cross_device_rename_falls_back_to_copy() {
+        let other_device = Path::new("/dev/shm");
+        let base = temp_dir("cross_device");
+        if !other_device.is_dir() || utils::same_volume(&base, other_device) {
+            fs::remove_dir_all(&base).unwrap();
+            return;
+        }
+        let source = make_source(&base);
+        let target = other_device.join(format!("adc_move_cross_device_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&target);
+        let (tx, _rx) = mpsc::channel();
+
+        move_folder_with(
+            &source,
+            &target,
+            true,
+            &link::SymlinkStrategy,
+            &AtomicBool::new(false),
+            &tx,
+        )
+        .unwrap();
+
+        assert!(move_journal::is_link(&source));
+        assert_eq!(
+            fs::read(target.join("Cache/nested/data.bin")).unwrap(),
+            vec![7u8; 300_000]
+        );
+        assert!(!move_journal::backup_path(&source).exists());
+        assert!(MoveJournal::load().is_none());
+
+        fs::remove_dir_all(&target).unwrap();
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn same_volume_move_renames_instead_of_copying() {
+        use std::os::unix::fs::MetadataExt;
+
+        let base = temp_dir("rename");
+        let source = make_source(&base);
+        let target = base.join("moved");
+        let inode = fs::metadata(source.join("settings.json")).unwrap().ino();
+
+        assert!(utils::same_volume(&source, &target));
+        run_move(&source, &target).unwrap();
+
+        // 重命名不会产生新文件，inode 保持不变
+        assert_eq!(
+            fs::metadata(target.join("settings.json")).unwrap().ino(),
+            inode
+        );
+        assert!(move_journal::is_link(&source));
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    fn copy_tree(source: &Path, target: &Path) {
+        let (tx, _rx) = mpsc::channel();
+        fs::create_dir_all(target).unwrap();
+        copy_dir_with_progress(source, target, None, &AtomicBool::new(false), &tx).unwrap();
+    }
+
+    #[test]
+    fn copy_preserves_file_and_directory_times() {
+        let base = temp_dir("times");
+        let source = make_source(&base);
+        let target = base.join("copy");
+        let file_mtime = filetime::FileTime::from_unix_time(1_500_000_000, 0);
+        let file_atime = filetime::FileTime::from_unix_time(1_600_000_000, 0);
+        let dir_mtime = filetime::FileTime::from_unix_time(1_400_000_000, 0);
+        filetime::set_file_times(source.join("settings.json"), file_atime, file_mtime).unwrap();
+        filetime::set_file_times(source.join("Cache/nested"), dir_mtime, dir_mtime).unwrap();
+
+        copy_tree(&source, &target);
+
+        let file = fs::metadata(target.join("settings.json")).unwrap();
+        assert_eq!(
+            filetime::FileTime::from_last_modification_time(&file),
+            file_mtime
+        );
+        assert_eq!(filetime::FileTime::from_last_access_time(&file), file_atime);
+        let dir = fs::metadata(target.join("Cache/nested")).unwrap();
+        assert_eq!(
+            filetime::FileTime::from_last_modification_time(&dir),
+            dir_mtime
+        );
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn copy_preserves_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let base = temp_dir("permissions");
+        let source = make_source(&base);
+        let target = base.join("copy");
+        fs::set_permissions(
+            source.join("settings.json"),
+            fs::Permissions::from_mode(0o640),
+        )
+        .unwrap();
+        fs::set_permissions(
+            source.join("Cache/nested/data.bin"),
+            fs::Permissions::from_mode(0o444),
+        )
+        .unwrap();
+        fs::set_permissions(source.join("Cache"), fs::Permissions::from_mode(0o750)).unwrap();
+
+        copy_tree(&source, &target);
+
+        let mode = |path: &str| {
+            fs::metadata(target.join(path))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o777
+        };
+        assert_eq!(mode("settings.json"), 0o640);
+        assert_eq!(mode("Cache/nested/data.bin"), 0o444);
+        assert_eq!(mode("Cache"), 0o750);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn copy_recreates_symlinks_inside_tree() {
+        let base = temp_dir("inner_links");
+        let source = make_source(&base);
+        let target = base.join("copy");
+        std::os::unix::fs::symlink("Cache/nested", source.join("relative")).unwrap();
+        std::os::unix::fs::symlink("missing.txt", source.join("broken")).unwrap();
+
+        copy_tree(&source, &target);
+
+        for name in ["relative", "broken"] {
+            assert!(move_journal::is_link(&target.join(name)));
+            assert_eq!(
+                fs::read_link(target.join(name)).unwrap(),
+                fs::read_link(source.join(name)).unwrap()
+            );
+        }
+        let mismatches =
+            utils::compare_dirs_hash(&source, &target, &AtomicBool::new(false)).unwrap();
+        assert!(mismatches.is_empty(), "{:?}", mismatches);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn copy_preserves_extended_attributes() {
+        let base = temp_dir("xattr");
+        let source = make_source(&base);
+        let target = base.join("copy");
+        // 部分文件系统（如 tmpfs 的旧版本）不支持 user 属性
+        if xattr::set(source.join("settings.json"), "user.adc_test", b"value").is_err() {
+            fs::remove_dir_all(&base).unwrap();
+            return;
+        }
+        xattr::set(source.join("Cache"), "user.adc_test", b"dir").unwrap();
+
+        copy_tree(&source, &target);
+
+        assert_eq!(
+            xattr::get(target.join("settings.json"), "user.adc_test").unwrap(),
+            Some(b"value".to_vec())
+        );
+        assert_eq!(
+            xattr::get(target.join("Cache"), "user.adc_test").unwrap(),
+            Some(b"dir".to_vec())
+        );
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn copy_recreates_fifo_without_reading_it() {
+        use std::os::unix::fs::FileTypeExt;
+
+        let base = temp_dir("fifo");
+        let source = make_source(&base);
+        let target = base.join("copy");
+        let fifo = std::ffi::CString::new(source.join("pipe").to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+
+        copy_tree(&source, &target);
+
+        assert!(fs::symlink_metadata(target.join("pipe"))
+            .unwrap()
+            .file_type()
+            .is_fifo());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn move_back_restores_folder_and_removes_target() {
+        for renamed in [true, false] {
+            let base = temp_dir(&format!("move_back_{}", renamed));
+            let source = make_source(&base);
+            let target = base.join("moved");
+            run_move(&source, &target).unwrap();
+            let target = fs::canonicalize(&target).unwrap();
+
+            let (tx, _rx) = mpsc::channel();
+            move_back_with(&source, &target, renamed, &AtomicBool::new(false), &tx).unwrap();
+
+            assert!(!move_journal::is_link(&source));
+            assert_eq!(fs::read(source.join("settings.json")).unwrap(), b"{}");
+            assert_eq!(
+                fs::read(source.join("Cache/nested/data.bin")).unwrap(),
+                vec![7u8; 300_000]
+            );
+            assert!(!target.exists());
+            assert!(!move_journal::backup_path(&source).exists());
+            assert!(MoveJournal::load().is_none());
+
+            fs::remove_dir_all(&base).unwrap();
+        }
+    }
+
+    #[test]
+    fn move_back_requires_matching_relocation_record() {
+        let base = temp_dir("move_back_record");
+        let source = make_source(&base);
+        let target = base.join("moved");
+        run_move(&source, &target).unwrap();
+        let other = base.join("other");
+        fs::create_dir_all(&other).unwrap();
+        let record = |target: &Path| relocation::Relocation {
+            source: source.clone(),
+            target: target.to_path_buf(),
+            link_kind: LinkKind::Symlink,
+            moved_at: String::new(),
+            size: 0,
+        };
+
+        let err = relocation::move_back_target(&[], &source).unwrap_err();
+        assert!(err.contains("没有移动记录"), "{}", err);
+        let err = relocation::move_back_target(&[record(&other)], &source).unwrap_err();
+        assert!(err.contains("不一致"), "{}", err);
+        assert_eq!(
+            relocation::move_back_target(&[record(&target)], &source).unwrap(),
+            fs::canonicalize(&target).unwrap()
+        );
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn cancelled_move_back_keeps_link() {
+        let base = temp_dir("move_back_cancel");
+        let source = make_source(&base);
+        let target = base.join("moved");
+        run_move(&source, &target).unwrap();
+        let target = fs::canonicalize(&target).unwrap();
+
+        let (tx, _rx) = mpsc::channel();
+        let err = move_back_with(&source, &target, false, &AtomicBool::new(true), &tx).unwrap_err();
+
+        assert!(err.contains("已回滚"), "{}", err);
+        assert!(move_journal::is_link(&source));
+        assert_eq!(fs::read(source.join("settings.json")).unwrap(), b"{}");
+        assert!(!move_journal::backup_path(&source).exists());
+        assert!(MoveJournal::load().is_none());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn interrupted_move_back_is_rolled_back_to_link() {
+        let base = temp_dir("move_back_crash");
+        let source = make_source(&base);
+        let target = base.join("moved");
+        run_move(&source, &target).unwrap();
+        let target = fs::canonicalize(&target).unwrap();
+
+        // 模拟删除链接、重命名之后程序退出
+        let mut journal = MoveJournal::new_move_back(&source, &target, true);
+        journal.set_stage(MoveStage::SourceRenamed).unwrap();
+        move_journal::remove_link(&source).unwrap();
+        fs::rename(&target, &source).unwrap();
+
+        assert!(move_journal::recover_interrupted_move().is_some());
+        assert!(move_journal::is_link(&source));
+        assert_eq!(fs::read(target.join("settings.json")).unwrap(), b"{}");
+        assert!(MoveJournal::load().is_none());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn resumed_copy_skips_intact_checkpointed_files() {
+        use std::os::unix::fs::MetadataExt;
+
+        let base = temp_dir("resume");
+        let source = make_source(&base);
+        fs::write(source.join("other.txt"), b"other").unwrap();
+        let target = base.join("copy");
+        fs::create_dir(&target).unwrap();
+        let (tx, _rx) = mpsc::channel();
+        let copy = |checkpoint: &mut MoveCheckpoint| {
+            copy_dir_with_progress(
+                &source,
+                &target,
+                Some(checkpoint),
+                &AtomicBool::new(false),
+                &tx,
+            )
+            .unwrap()
+        };
+        copy(&mut MoveCheckpoint::open(&source, &target).unwrap());
+
+        // 一个文件还没复制，一个文件已记录但内容损坏
+        let inode = |name: &str| fs::metadata(target.join(name)).unwrap().ino();
+        let kept = inode("settings.json");
+        fs::remove_file(target.join("Cache/nested/data.bin")).unwrap();
+        fs::write(target.join("other.txt"), b"OTHER").unwrap();
+        assert!(move_checkpoint::exists_for(&source, &target));
+
+        copy(&mut MoveCheckpoint::open(&source, &target).unwrap());
+
+        assert_eq!(inode("settings.json"), kept);
+        assert_eq!(fs::read(target.join("other.txt")).unwrap(), b"other");
+        let mismatches =
+            utils::compare_dirs_hash(&source, &target, &AtomicBool::new(false)).unwrap();
+        assert!(mismatches.is_empty(), "{:?}", mismatches);
+
+        move_checkpoint::clear_for(&source, &target);
+        assert!(!move_checkpoint::exists_for(&source, &target));
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn interrupted_copy_is_kept_for_resume() {
+        let base = temp_dir("resume_crash");
+        let source = make_source(&base);
+        let target = base.join("moved");
+        fs::create_dir(&target).unwrap();
+        fs::write(target.join("settings.json"), b"{}").unwrap();
+        MoveJournal::new(&source, &target, true, false)
+            .save()
+            .unwrap();
+        drop(MoveCheckpoint::open(&source, &target).unwrap());
+
+        let message = move_journal::recover_interrupted_move().unwrap();
+
+        assert!(message.contains("继续"), "{}", message);
+        assert!(MoveJournal::load().is_none());
+        assert!(target.join("settings.json").exists());
+        assert!(move_checkpoint::exists_for(&source, &target));
+
+        move_checkpoint::clear_for(&source, &target);
+        fs::remove_dir_all(&base).unwrap();
+    }
 }
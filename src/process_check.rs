@@ -0,0 +1,291 @@
+use crate::logger;
+use eframe::egui;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+/// 正在使用某个文件夹的进程
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+}
+
+/// 查找打开了 `folder` 中文件、以它为工作目录或从它启动的进程
+pub fn find_processes_using(folder: &Path) -> Vec<ProcessInfo> {
+    // 统一使用规范路径比较，避免符号链接造成误判
+    let folder = folder
+        .canonicalize()
+        .unwrap_or_else(|_| folder.to_path_buf());
+    let mut processes = find_processes_impl(&folder);
+    processes.sort_by_key(|p| p.pid);
+    processes.dedup_by_key(|p| p.pid);
+    processes
+}
+
+// Linux: 检查 /proc/<pid>/fd、/proc/<pid>/cwd 和 /proc/<pid>/exe
+#[cfg(target_os = "linux")]
+fn find_processes_impl(folder: &Path) -> Vec<ProcessInfo> {
+    use std::fs;
+
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    let links_into_folder = |link: &Path| {
+        fs::read_link(link)
+            .map(|target| target.starts_with(folder))
+            .unwrap_or(false)
+    };
+
+    let mut processes = Vec::new();
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let proc_dir = entry.path();
+
+        // 没有权限读取的进程会被跳过
+        let in_use = links_into_folder(&proc_dir.join("cwd"))
+            || links_into_folder(&proc_dir.join("exe"))
+            || fs::read_dir(proc_dir.join("fd"))
+                .map(|fds| fds.flatten().any(|fd| links_into_folder(&fd.path())))
+                .unwrap_or(false);
+
+        if in_use {
+            let name = fs::read_to_string(proc_dir.join("comm"))
+                .map(|name| name.trim().to_string())
+                .unwrap_or_else(|_| pid.to_string());
+            processes.push(ProcessInfo { pid, name });
+        }
+    }
+    processes
+}
+
+// Windows: 通过 Restart Manager 查询占用文件夹中文件的进程
+#[cfg(windows)]
+fn find_processes_impl(folder: &Path) -> Vec<ProcessInfo> {
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+    use walkdir::WalkDir;
+    use windows_sys::Win32::Foundation::{ERROR_MORE_DATA, ERROR_SUCCESS};
+    use windows_sys::Win32::System::RestartManager::{
+        RmEndSession, RmGetList, RmRegisterResources, RmStartSession, CCH_RM_SESSION_KEY,
+        RM_PROCESS_INFO,
+    };
+
+    // 每次注册的文件数量，避免一次传入过多路径
+    const BATCH_SIZE: usize = 1000;
+
+    // Restart Manager 只接受文件路径，需要先列出文件夹中的所有文件
+    let files: Vec<Vec<u16>> = WalkDir::new(folder)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| {
+            entry
+                .path()
+                .as_os_str()
+                .encode_wide()
+                .chain(Some(0))
+                .collect()
+        })
+        .collect();
+    if files.is_empty() {
+        return Vec::new();
+    }
+
+    let mut session = 0u32;
+    let mut session_key = [0u16; CCH_RM_SESSION_KEY as usize + 1];
+    let mut processes = Vec::new();
+
+    unsafe {
+        if RmStartSession(&mut session, 0, session_key.as_mut_ptr()) != ERROR_SUCCESS {
+            return processes;
+        }
+
+        let registered = files.chunks(BATCH_SIZE).all(|batch| {
+            let names: Vec<*const u16> = batch.iter().map(|name| name.as_ptr()).collect();
+            RmRegisterResources(
+                session,
+                names.len() as u32,
+                names.as_ptr(),
+                0,
+                ptr::null(),
+                0,
+                ptr::null(),
+            ) == ERROR_SUCCESS
+        });
+
+        if registered {
+            let mut infos: Vec<RM_PROCESS_INFO> = Vec::new();
+            loop {
+                let mut needed = 0u32;
+                let mut count = infos.len() as u32;
+                let mut reasons = 0u32;
+                let status = RmGetList(
+                    session,
+                    &mut needed,
+                    &mut count,
+                    infos.as_mut_ptr(),
+                    &mut reasons,
+                );
+                if status == ERROR_MORE_DATA {
+                    infos.resize(needed as usize, RM_PROCESS_INFO::default());
+                    continue;
+                }
+                if status == ERROR_SUCCESS {
+                    infos.truncate(count as usize);
+                    processes = infos
+                        .iter()
+                        .map(|info| {
+                            let len = info
+                                .strAppName
+                                .iter()
+                                .position(|&c| c == 0)
+                                .unwrap_or(info.strAppName.len());
+                            ProcessInfo {
+                                pid: info.Process.dwProcessId,
+                                name: String::from_utf16_lossy(&info.strAppName[..len]),
+                            }
+                        })
+                        .collect();
+                }
+                break;
+            }
+        }
+
+        RmEndSession(session);
+    }
+
+    processes
+}
+
+// 其他系统暂不支持检测
+#[cfg(not(any(target_os = "linux", windows)))]
+fn find_processes_impl(_folder: &Path) -> Vec<ProcessInfo> {
+    Vec::new()
+}
+
+/// 显示占用警告，返回 Some(true) 表示仍然继续，Some(false) 表示中止
+pub fn show_process_warning(
+    ctx: &egui::Context,
+    action: &str,
+    processes: &[ProcessInfo],
+) -> Option<bool> {
+    let mut result = None;
+
+    egui::Window::new("文件夹正在被使用")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "以下进程正在使用该文件夹，继续{}可能会损坏程序数据：",
+                action
+            ));
+            for process in processes {
+                ui.label(format!("{} (PID {})", process.name, process.pid));
+            }
+            ui.label("建议先关闭这些程序。");
+
+            ui.horizontal(|ui| {
+                if ui.button("中止").clicked() {
+                    result = Some(false);
+                }
+                if ui.button(format!("仍然{}", action)).clicked() {
+                    result = Some(true);
+                }
+            });
+        });
+
+    result
+}
+
+/// 在后台线程中查找占用文件夹的进程，避免遍历 /proc 或文件列表时卡住界面。
+/// 找到占用进程后显示警告，由用户决定是否继续
+pub struct ProcessCheck {
+    folder: PathBuf,
+    action: String,
+    rx: Receiver<Vec<ProcessInfo>>,
+    processes: Option<Vec<ProcessInfo>>,
+}
+
+impl ProcessCheck {
+    pub fn start(folder: PathBuf, action: &str) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let worker_folder = folder.clone();
+        thread::spawn(move || {
+            let _ = tx.send(find_processes_using(&worker_folder));
+        });
+
+        Self {
+            folder,
+            action: action.to_string(),
+            rx,
+            processes: None,
+        }
+    }
+
+    /// 显示检查进度或占用警告。
+    /// 返回 Some(true) 表示没有占用或用户选择继续，Some(false) 表示中止，None 表示尚未决定
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<bool> {
+        if self.processes.is_none() {
+            match self.rx.try_recv() {
+                Ok(processes) if processes.is_empty() => return Some(true),
+                Ok(processes) => {
+                    logger::log_info(&format!(
+                        "文件夹 {} 正在被 {} 个进程使用",
+                        self.folder.display(),
+                        processes.len()
+                    ));
+                    self.processes = Some(processes);
+                }
+                Err(TryRecvError::Empty) => {
+                    egui::Window::new("正在检查")
+                        .collapsible(false)
+                        .resizable(false)
+                        .show(ctx, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.spinner();
+                                ui.label("正在检查是否有程序在使用该文件夹...");
+                            });
+                        });
+                    ctx.request_repaint();
+                    return None;
+                }
+                // 检查线程异常退出，按没有占用处理
+                Err(TryRecvError::Disconnected) => return Some(true),
+            }
+        }
+
+        let processes = self.processes.as_deref().unwrap_or_default();
+        show_process_warning(ctx, &self.action, processes)
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn detects_file_held_open_by_current_process() {
+        let dir = std::env::temp_dir().join(format!("adc_process_check_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let held = fs::File::create(dir.join("held.txt")).unwrap();
+
+        let processes = find_processes_using(&dir);
+        assert!(processes.iter().any(|p| p.pid == std::process::id()));
+
+        drop(held);
+        assert!(!find_processes_using(&dir)
+            .iter()
+            .any(|p| p.pid == std::process::id()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
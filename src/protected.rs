@@ -0,0 +1,242 @@
+use crate::app_dirs;
+use crate::logger;
+use crate::utils;
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+pub const PROTECTED_FILE: &str = "protected_folders.yaml";
+
+const APPDATA_ROOTS: [&str; 3] = ["Roaming", "Local", "LocalLow"];
+
+/// 保护级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Confirm, // 需要输入文件夹名确认
+    Blocked, // 禁止删除或移动
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtectedEntry {
+    pub name: String,
+    pub severity: Severity,
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// 用户自定义的受保护文件夹，按根目录分类
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ProtectedFolders {
+    #[serde(rename = "Roaming")]
+    roaming: Vec<ProtectedEntry>,
+    #[serde(rename = "Local")]
+    local: Vec<ProtectedEntry>,
+    #[serde(rename = "LocalLow")]
+    local_low: Vec<ProtectedEntry>,
+}
+
+impl ProtectedFolders {
+    fn entries_mut(&mut self, root: &str) -> Option<&mut Vec<ProtectedEntry>> {
+        match root {
+            "Roaming" => Some(&mut self.roaming),
+            "Local" => Some(&mut self.local),
+            "LocalLow" => Some(&mut self.local_low),
+            _ => None,
+        }
+    }
+
+    fn entries(&self, root: &str) -> &[ProtectedEntry] {
+        match root {
+            "Roaming" => &self.roaming,
+            "Local" => &self.local,
+            "LocalLow" => &self.local_low,
+            _ => &[],
+        }
+    }
+}
+
+// 内置的受保护文件夹：(根目录, 文件夹名, 级别, 原因)
+const BUILTIN: &[(&str, &str, Severity, &str)] = &[
+    (
+        "Roaming",
+        "Microsoft",
+        Severity::Blocked,
+        "Windows 系统组件数据",
+    ),
+    (
+        "Local",
+        "Microsoft",
+        Severity::Blocked,
+        "Windows 系统组件数据",
+    ),
+    ("Local", "Packages", Severity::Blocked, "应用商店应用数据"),
+    (
+        "Local",
+        "Programs",
+        Severity::Confirm,
+        "按用户安装的程序本体",
+    ),
+    (
+        "Local",
+        "Temp",
+        Severity::Confirm,
+        "系统临时目录，正在运行的程序可能在使用",
+    ),
+    (
+        "Local",
+        "ConnectedDevicesPlatform",
+        Severity::Confirm,
+        "Windows 设备同步数据",
+    ),
+    (
+        "Local",
+        "Comms",
+        Severity::Confirm,
+        "Windows 邮件和日历数据",
+    ),
+    (
+        "LocalLow",
+        "Microsoft",
+        Severity::Confirm,
+        "Windows 系统组件数据",
+    ),
+    // Linux 下 Roaming 对应 ~/.local/share，Local 对应 ~/.cache
+    (
+        "Roaming",
+        "systemd",
+        Severity::Blocked,
+        "systemd 用户服务数据",
+    ),
+    ("Roaming", "keyrings", Severity::Blocked, "系统密钥环"),
+    ("Roaming", "applications", Severity::Confirm, "桌面启动器"),
+    ("Roaming", "Trash", Severity::Confirm, "回收站"),
+];
+
+// 内置列表与用户列表合并后的结果，只加载一次
+fn protected_folders() -> &'static ProtectedFolders {
+    static PROTECTED: OnceLock<ProtectedFolders> = OnceLock::new();
+    PROTECTED.get_or_init(|| {
+        let mut folders = load_user_protected_folders();
+        for (root, name, severity, reason) in BUILTIN {
+            if let Some(entries) = folders.entries_mut(root) {
+                entries.push(ProtectedEntry {
+                    name: name.to_string(),
+                    severity: *severity,
+                    reason: reason.to_string(),
+                });
+            }
+        }
+        folders
+    })
+}
+
+fn load_user_protected_folders() -> ProtectedFolders {
+    let Ok(content) = fs::read_to_string(app_dirs::config_file(PROTECTED_FILE)) else {
+        return ProtectedFolders::default();
+    };
+    serde_yaml::from_str(&content).unwrap_or_else(|e| {
+        logger::log_error(&format!("解析受保护文件夹列表失败: {}", e));
+        ProtectedFolders::default()
+    })
+}
+
+/// 检查根目录下的文件夹是否受保护，匹配多项时返回最严格的一项
+pub fn check(root: &str, folder_name: &str) -> Option<ProtectedEntry> {
+    protected_folders()
+        .entries(root)
+        .iter()
+        .filter(|entry| entry.name.eq_ignore_ascii_case(folder_name))
+        .max_by_key(|entry| entry.severity)
+        .cloned()
+}
+
+/// 按完整路径检查。AppData 根目录本身及其上级目录一律禁止
+pub fn check_path(path: &Path) -> Option<ProtectedEntry> {
+    for root in APPDATA_ROOTS {
+        let Some(base_path) = utils::get_appdata_dir(root) else {
+            continue;
+        };
+        if base_path.starts_with(path) {
+            return Some(ProtectedEntry {
+                name: path.display().to_string(),
+                severity: Severity::Blocked,
+                reason: format!("{} 根目录", root),
+            });
+        }
+        if path.parent() == Some(base_path.as_path()) {
+            if let Some(entry) = path
+                .file_name()
+                .and_then(|name| check(root, &name.to_string_lossy()))
+            {
+                return Some(entry);
+            }
+        }
+    }
+    None
+}
+
+/// 检查是否允许对该路径执行操作，`confirmed` 表示用户已输入名称确认
+pub fn ensure_allowed(path: &Path, action: &str, confirmed: bool) -> Result<(), String> {
+    match check_path(path) {
+        Some(entry) if entry.severity == Severity::Blocked => Err(format!(
+            "文件夹 {} 受保护（{}），禁止{}",
+            entry.name, entry.reason, action
+        )),
+        Some(entry) if !confirmed => Err(format!(
+            "文件夹 {} 受保护（{}），需要输入文件夹名确认后才能{}",
+            entry.name, entry.reason, action
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// 受保护文件夹的确认窗口，需要输入文件夹名才能继续。
+/// 返回 Some(true) 表示确认，Some(false) 表示取消
+pub fn show_typed_confirmation(
+    ctx: &egui::Context,
+    action: &str,
+    entry: &ProtectedEntry,
+) -> Option<bool> {
+    let mut result = None;
+    let input_id = egui::Id::new(("protected_confirmation", &entry.name));
+    let mut typed: String = ctx.data_mut(|data| data.get_temp(input_id).unwrap_or_default());
+
+    egui::Window::new("受保护的文件夹")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(format!("文件夹 {} 受保护: {}", entry.name, entry.reason));
+            ui.label(format!(
+                "{}后相关程序可能无法正常工作。如确定要继续，请输入文件夹名:",
+                action
+            ));
+            ui.text_edit_singleline(&mut typed);
+
+            ui.horizontal(|ui| {
+                let matches = typed == entry.name;
+                if ui
+                    .add_enabled(matches, egui::Button::new(format!("确认{}", action)))
+                    .clicked()
+                {
+                    result = Some(true);
+                }
+                if ui.button("取消").clicked() {
+                    result = Some(false);
+                }
+            });
+        });
+
+    ctx.data_mut(|data| {
+        if result.is_some() {
+            data.remove::<String>(input_id);
+        } else {
+            data.insert_temp(input_id, typed);
+        }
+    });
+
+    result
+}
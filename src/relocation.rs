@@ -0,0 +1,329 @@
+use crate::app_dirs;
+use crate::link::{self, LinkKind};
+use crate::logger;
+use crate::move_journal;
+use crate::utils;
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+pub const RELOCATIONS_FILE: &str = "relocations.yaml";
+
+/// 一次移动的记录，`source` 为留下链接的原路径，`target` 为数据所在的目录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Relocation {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub link_kind: LinkKind,
+    pub moved_at: String,
+    pub size: u64,
+}
+
+pub fn load_relocations() -> Vec<Relocation> {
+    let Ok(content) = fs::read_to_string(app_dirs::config_file(RELOCATIONS_FILE)) else {
+        return Vec::new();
+    };
+    serde_yaml::from_str(&content).unwrap_or_else(|e| {
+        logger::log_error(&format!("解析移动记录失败: {}", e));
+        Vec::new()
+    })
+}
+
+pub fn save_relocations(relocations: &[Relocation]) {
+    match serde_yaml::to_string(relocations) {
+        Ok(content) => {
+            if let Err(e) = fs::write(app_dirs::config_file(RELOCATIONS_FILE), content) {
+                logger::log_error(&format!("保存移动记录失败: {}", e));
+            }
+        }
+        Err(e) => logger::log_error(&format!("序列化移动记录失败: {}", e)),
+    }
+}
+
+/// 移动成功后记录，同一个原路径只保留最新的一条
+pub fn record_move(source: &Path, target: &Path, link_kind: LinkKind, size: u64) {
+    let mut relocations = load_relocations();
+    relocations.retain(|r| r.source != source);
+    relocations.push(Relocation {
+        source: source.to_path_buf(),
+        target: target.to_path_buf(),
+        link_kind,
+        moved_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        size,
+    });
+    save_relocations(&relocations);
+}
+
+/// 移回或不再需要跟踪时删除记录
+pub fn forget(source: &Path) {
+    let mut relocations = load_relocations();
+    let count = relocations.len();
+    relocations.retain(|r| r.source != source);
+    if relocations.len() != count {
+        save_relocations(&relocations);
+    }
+}
+
+/// 已移动文件夹的链接状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkHealth {
+    Healthy,
+    DriveNotMounted, // 目标所在的驱动器不存在，可能是移动硬盘未连接
+    TargetMissing,   // 驱动器在，但目标目录已被删除
+    LinkMissing,     // 原路径上没有链接
+    LinkBroken,      // 原路径是链接，但没有指向目标目录
+    NotLinked,       // 原路径是普通文件夹，可能已被手动移回
+}
+
+impl LinkHealth {
+    pub fn label(self) -> &'static str {
+        match self {
+            LinkHealth::Healthy => "正常",
+            LinkHealth::DriveNotMounted => "目标驱动器未连接",
+            LinkHealth::TargetMissing => "目标目录不存在",
+            LinkHealth::LinkMissing => "链接已丢失",
+            LinkHealth::LinkBroken => "链接已损坏",
+            LinkHealth::NotLinked => "原位置已是普通文件夹",
+        }
+    }
+
+    /// 目标数据还在、可以重建链接
+    pub fn repairable(self) -> bool {
+        matches!(self, LinkHealth::LinkMissing | LinkHealth::LinkBroken)
+    }
+}
+
+// 路径所在的驱动器根目录，如 D:\。Unix 上总是 /，因此只能在 Windows 上识别未连接的驱动器
+fn volume_root(path: &Path) -> PathBuf {
+    path.components()
+        .take_while(|c| matches!(c, Component::Prefix(_) | Component::RootDir))
+        .collect()
+}
+
+pub fn check_health(relocation: &Relocation) -> LinkHealth {
+    let root = volume_root(&relocation.target);
+    if !root.as_os_str().is_empty() && !root.exists() {
+        return LinkHealth::DriveNotMounted;
+    }
+    if !relocation.target.is_dir() {
+        return LinkHealth::TargetMissing;
+    }
+    if !move_journal::is_link(&relocation.source) {
+        return if relocation.source.exists() {
+            LinkHealth::NotLinked
+        } else {
+            LinkHealth::LinkMissing
+        };
+    }
+    let resolved = fs::canonicalize(&relocation.source).ok();
+    if resolved.is_some() && resolved == fs::canonicalize(&relocation.target).ok() {
+        LinkHealth::Healthy
+    } else {
+        LinkHealth::LinkBroken
+    }
+}
+
+/// 移回前确认原路径有移动记录，且链接仍指向记录的目标目录，返回目标目录的实际路径
+pub fn move_back_target(relocations: &[Relocation], source: &Path) -> Result<PathBuf, String> {
+    let relocation = relocations
+        .iter()
+        .find(|r| r.source == source)
+        .ok_or_else(|| format!("{} 没有移动记录，不能移回", source.display()))?;
+    match check_health(relocation) {
+        LinkHealth::Healthy => {
+            fs::canonicalize(&relocation.target).map_err(|e| format!("链接指向的目录不可用: {}", e))
+        }
+        health => Err(format!(
+            "{} 与移动记录不一致（{}），不能移回",
+            source.display(),
+            health.label()
+        )),
+    }
+}
+
+/// 删除损坏的链接，按记录的类型重新创建指向目标目录的链接
+pub fn repair_link(relocation: &Relocation) -> Result<String, String> {
+    let health = check_health(relocation);
+    if !health.repairable() {
+        return Err(format!("当前状态无法重建链接: {}", health.label()));
+    }
+    if move_journal::is_link(&relocation.source) {
+        move_journal::remove_link(&relocation.source)
+            .map_err(|e| format!("无法删除损坏的链接: {}", e))?;
+    }
+    link::strategy_for(relocation.link_kind).create(&relocation.source, &relocation.target)?;
+
+    let message = format!(
+        "已重建{}: {} -> {}",
+        relocation.link_kind.label(),
+        relocation.source.display(),
+        relocation.target.display()
+    );
+    logger::log_audit(&message);
+    Ok(message)
+}
+
+/// “已移动的文件夹”窗口，列出所有记录和链接状态
+#[derive(Default)]
+pub struct RelocationWindow {
+    pub show_window: bool,
+    entries: Vec<(Relocation, LinkHealth)>,
+    status_message: Option<String>,
+}
+
+impl RelocationWindow {
+    pub fn open(&mut self) {
+        self.show_window = true;
+        self.status_message = None;
+        self.refresh();
+    }
+
+    fn refresh(&mut self) {
+        self.entries = load_relocations()
+            .into_iter()
+            .map(|r| {
+                let health = check_health(&r);
+                (r, health)
+            })
+            .collect();
+    }
+
+    pub fn show_relocation_window(&mut self, ctx: &egui::Context) {
+        if !self.show_window {
+            return;
+        }
+
+        let mut open = true;
+        let mut repair = None;
+        let mut remove = None;
+        egui::Window::new("已移动的文件夹")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                if self.entries.is_empty() {
+                    ui.label("还没有移动过文件夹");
+                }
+
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("relocations").striped(true).show(ui, |ui| {
+                            ui.label("原位置");
+                            ui.label("新位置");
+                            ui.label("链接类型");
+                            ui.label("移动时间");
+                            ui.label("大小");
+                            ui.label("状态");
+                            ui.label("操作");
+                            ui.end_row();
+
+                            for (index, (relocation, health)) in self.entries.iter().enumerate() {
+                                ui.label(relocation.source.display().to_string());
+                                ui.label(relocation.target.display().to_string());
+                                ui.label(relocation.link_kind.label());
+                                ui.label(&relocation.moved_at);
+                                ui.label(utils::format_size(relocation.size));
+                                if *health == LinkHealth::Healthy {
+                                    ui.label(health.label());
+                                } else {
+                                    ui.colored_label(egui::Color32::RED, health.label());
+                                }
+                                ui.horizontal(|ui| {
+                                    if health.repairable() && ui.button("重建链接").clicked() {
+                                        repair = Some(index);
+                                    }
+                                    if matches!(
+                                        health,
+                                        LinkHealth::TargetMissing | LinkHealth::NotLinked
+                                    ) && ui.button("移除记录").clicked()
+                                    {
+                                        remove = Some(index);
+                                    }
+                                });
+                                ui.end_row();
+                            }
+                        });
+                    });
+
+                if let Some(message) = &self.status_message {
+                    ui.label(message);
+                }
+                if ui.button("重新检查").clicked() {
+                    self.refresh();
+                }
+            });
+        if !open {
+            self.show_window = false;
+        }
+
+        if let Some(index) = repair {
+            let result = repair_link(&self.entries[index].0);
+            if let Err(err) = &result {
+                logger::log_error(err);
+            }
+            self.status_message = Some(result.unwrap_or_else(|err| err));
+            self.refresh();
+        }
+        if let Some(index) = remove {
+            let source = self.entries[index].0.source.clone();
+            forget(&source);
+            logger::log_info(&format!("已移除移动记录: {}", source.display()));
+            self.refresh();
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn relocation(name: &str) -> Relocation {
+        let base =
+            std::env::temp_dir().join(format!("adc_relocation_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(base.join("moved")).unwrap();
+        Relocation {
+            source: base.join("App"),
+            target: base.join("moved"),
+            link_kind: LinkKind::Symlink,
+            moved_at: String::new(),
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn health_reflects_link_and_target_state() {
+        let r = relocation("health");
+        let base = r.source.parent().unwrap().to_path_buf();
+
+        assert_eq!(check_health(&r), LinkHealth::LinkMissing);
+        std::os::unix::fs::symlink(&r.target, &r.source).unwrap();
+        assert_eq!(check_health(&r), LinkHealth::Healthy);
+
+        fs::remove_file(&r.source).unwrap();
+        std::os::unix::fs::symlink(base.join("elsewhere"), &r.source).unwrap();
+        assert_eq!(check_health(&r), LinkHealth::LinkBroken);
+
+        fs::remove_file(&r.source).unwrap();
+        fs::create_dir(&r.source).unwrap();
+        assert_eq!(check_health(&r), LinkHealth::NotLinked);
+
+        fs::remove_dir(&r.target).unwrap();
+        assert_eq!(check_health(&r), LinkHealth::TargetMissing);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn repair_recreates_broken_link() {
+        let r = relocation("repair");
+        let base = r.source.parent().unwrap().to_path_buf();
+        std::os::unix::fs::symlink(base.join("elsewhere"), &r.source).unwrap();
+
+        repair_link(&r).unwrap();
+
+        assert_eq!(check_health(&r), LinkHealth::Healthy);
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,618 @@
+use crate::archive::{self, ArchiveManifest, ArchiveProgress};
+use crate::logger;
+use crate::process_check;
+use crate::scanner;
+use crate::utils;
+use eframe::egui;
+use native_dialog::FileDialog;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use zip::ZipArchive;
+
+// 每次解压的块大小，同时也是进度更新和检查取消的间隔
+const COPY_BUFFER_SIZE: usize = 256 * 1024;
+
+// 冲突列表中最多显示的文件数
+const CONFLICT_PREVIEW_LIMIT: usize = 50;
+
+/// 目标位置已存在同名文件时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictMode {
+    #[default]
+    Skip, // 保留现有文件
+    Overwrite, // 用备份中的文件覆盖
+    Rename,    // 备份中的文件另存为 "名称 (1).扩展名"
+}
+
+impl ConflictMode {
+    fn label(self) -> &'static str {
+        match self {
+            ConflictMode::Skip => "跳过",
+            ConflictMode::Overwrite => "覆盖",
+            ConflictMode::Rename => "重命名",
+        }
+    }
+}
+
+/// 还原结果
+#[derive(Debug, Clone, Default)]
+pub struct RestoreReport {
+    pub target: PathBuf,
+    pub files_restored: u64,
+    pub files_skipped: u64,
+    pub files_renamed: u64,
+    pub bytes_restored: u64,
+    pub cancelled: bool,
+    pub folder_size: u64, // 还原后目标文件夹的总大小
+}
+
+impl RestoreReport {
+    pub fn summary(&self) -> String {
+        let mut message = format!(
+            "已还原 {} 个文件（{}）到 {}",
+            utils::format_count(self.files_restored),
+            utils::format_size(self.bytes_restored),
+            self.target.display()
+        );
+        if self.files_skipped > 0 {
+            message.push_str(&format!(
+                "，跳过 {} 个已存在的文件",
+                utils::format_count(self.files_skipped)
+            ));
+        }
+        if self.files_renamed > 0 {
+            message.push_str(&format!(
+                "，{} 个文件已重命名",
+                utils::format_count(self.files_renamed)
+            ));
+        }
+        if self.cancelled {
+            message = format!("已取消，{}", message);
+        }
+        message
+    }
+}
+
+// 清单来自压缩包，可能被篡改，使用前检查版本和文件夹名
+fn check_manifest(manifest: &ArchiveManifest) -> Result<(), String> {
+    if manifest.version == 0 || manifest.version > archive::MANIFEST_VERSION {
+        return Err(format!(
+            "不支持的备份版本 {}，请使用更新版本的程序还原",
+            manifest.version
+        ));
+    }
+    let mut components = Path::new(&manifest.folder_name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(()),
+        _ => Err(format!(
+            "压缩包中的文件夹名不安全: {}",
+            manifest.folder_name
+        )),
+    }
+}
+
+/// 备份应还原到的位置：优先使用当前系统的同名根目录，找不到时使用原始路径。
+/// 原始路径必须是以该文件夹名结尾、不含 ".." 的绝对路径
+pub fn restore_target(manifest: &ArchiveManifest) -> Result<PathBuf, String> {
+    check_manifest(manifest)?;
+    if let Some(base) = utils::get_appdata_dir(&manifest.root) {
+        return Ok(base.join(&manifest.folder_name));
+    }
+
+    let original = &manifest.original_path;
+    let is_safe = original.is_absolute()
+        && !original
+            .components()
+            .any(|c| matches!(c, Component::ParentDir | Component::CurDir))
+        && original.file_name() == Some(manifest.folder_name.as_ref());
+    if is_safe {
+        Ok(original.clone())
+    } else {
+        Err(format!(
+            "找不到根目录 {}，且原始路径无效: {}",
+            manifest.root,
+            original.display()
+        ))
+    }
+}
+
+// 清单中的路径只能是普通的相对路径，防止解压到目标目录之外
+fn check_relative_path(name: &str) -> Result<(), String> {
+    let path = Path::new(name);
+    if name.is_empty() || !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(format!("压缩包中包含不安全的路径: {}", name));
+    }
+    Ok(())
+}
+
+/// 列出目标位置中已经存在的文件
+pub fn find_conflicts(manifest: &ArchiveManifest, target: &Path) -> Vec<String> {
+    manifest
+        .files
+        .iter()
+        .filter(|entry| target.join(&entry.path).exists())
+        .map(|entry| entry.path.clone())
+        .collect()
+}
+
+// 找到一个不存在的文件名: "name (1).ext"、"name (2).ext" ...
+fn renamed_path(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// 校验压缩包后把其中的文件解压到 `target`
+pub fn restore_archive(
+    archive_path: &Path,
+    target: &Path,
+    mode: ConflictMode,
+    cancel_flag: &AtomicBool,
+    mut on_progress: impl FnMut(&ArchiveProgress),
+) -> Result<RestoreReport, String> {
+    let manifest = archive::verify_archive(archive_path, cancel_flag, &mut on_progress)?;
+    check_manifest(&manifest)?;
+
+    // 先检查所有路径，避免解压到一半才发现问题
+    for name in manifest
+        .directories
+        .iter()
+        .chain(manifest.files.iter().map(|f| &f.path))
+    {
+        check_relative_path(name)?;
+    }
+
+    let file = File::open(archive_path).map_err(|e| format!("无法打开压缩包: {}", e))?;
+    let mut zip = ZipArchive::new(file).map_err(|e| format!("不是有效的压缩包: {}", e))?;
+
+    fs::create_dir_all(target).map_err(|e| format!("无法创建目标目录: {}", e))?;
+    for dir in &manifest.directories {
+        fs::create_dir_all(target.join(dir))
+            .map_err(|e| format!("无法创建目录: {} - {}", dir, e))?;
+    }
+
+    let mut report = RestoreReport {
+        target: target.to_path_buf(),
+        ..Default::default()
+    };
+    let mut progress = ArchiveProgress {
+        stage: "正在还原".to_string(),
+        bytes_total: manifest.total_size(),
+        ..Default::default()
+    };
+    let mut buffer = vec![0u8; COPY_BUFFER_SIZE];
+
+    'files: for entry in &manifest.files {
+        progress.current_path = entry.path.clone();
+        let mut dest = target.join(&entry.path);
+        if dest.exists() {
+            match mode {
+                ConflictMode::Skip => {
+                    report.files_skipped += 1;
+                    progress.bytes_done += entry.size;
+                    on_progress(&progress);
+                    continue;
+                }
+                ConflictMode::Overwrite => {}
+                ConflictMode::Rename => {
+                    dest = renamed_path(&dest);
+                    report.files_renamed += 1;
+                }
+            }
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("无法创建目录: {}", e))?;
+        }
+
+        let mut data = zip
+            .by_name(&format!("{}{}", archive::DATA_PREFIX, entry.path))
+            .map_err(|_| format!("压缩包中缺少文件: {}", entry.path))?;
+        let mut output =
+            File::create(&dest).map_err(|e| format!("无法写入文件: {} - {}", entry.path, e))?;
+        loop {
+            if cancel_flag.load(Ordering::Relaxed) {
+                // 删除写了一半的文件，已完整还原的文件保留
+                drop(output);
+                let _ = fs::remove_file(&dest);
+                report.cancelled = true;
+                break 'files;
+            }
+            let read = data
+                .read(&mut buffer)
+                .map_err(|e| format!("读取压缩包失败: {} - {}", entry.path, e))?;
+            if read == 0 {
+                break;
+            }
+            output
+                .write_all(&buffer[..read])
+                .map_err(|e| format!("无法写入文件: {} - {}", entry.path, e))?;
+            progress.bytes_done += read as u64;
+            on_progress(&progress);
+        }
+
+        report.files_restored += 1;
+        report.bytes_restored += entry.size;
+    }
+
+    logger::log_audit(&format!(
+        "从 {} 还原（冲突处理: {}）: {}",
+        archive_path.display(),
+        mode.label(),
+        report.summary()
+    ));
+    Ok(report)
+}
+
+enum RestoreMessage {
+    Opened(Result<(ArchiveManifest, PathBuf, Vec<String>), String>),
+    Progress(ArchiveProgress),
+    Finished(Result<RestoreReport, String>),
+}
+
+/// "从备份还原"窗口
+#[derive(Default)]
+pub struct RestoreModule {
+    pub show_window: bool,
+    archive_path: Option<PathBuf>,
+    manifest: Option<ArchiveManifest>,
+    target: Option<PathBuf>, // 根据清单确定的还原位置
+    conflicts: Vec<String>,
+    mode: ConflictMode,
+    progress: ArchiveProgress,
+    status_message: Option<String>,
+    is_busy: bool,
+    rx: Option<Receiver<RestoreMessage>>,
+    cancel_flag: Arc<AtomicBool>,
+    process_scan: Option<process_check::ProcessCheck>,
+    restored: Option<(String, String, u64)>, // 已还原的 (根目录, 文件夹名, 还原后大小)，待同步到列表
+}
+
+impl RestoreModule {
+    pub fn open(&mut self) {
+        self.show_window = true;
+    }
+
+    /// 取出已还原的文件夹，用于更新主界面列表
+    pub fn take_restored(&mut self) -> Option<(String, String, u64)> {
+        self.restored.take()
+    }
+
+    // 在后台读取清单并检查冲突，不校验文件内容
+    fn open_archive(&mut self, archive_path: PathBuf) {
+        let (tx, rx) = mpsc::channel();
+        self.rx = Some(rx);
+        self.manifest = None;
+        self.target = None;
+        self.conflicts.clear();
+        self.is_busy = true;
+        self.status_message = Some("正在读取压缩包...".to_string());
+        self.archive_path = Some(archive_path.clone());
+
+        thread::spawn(move || {
+            let result = archive::read_manifest(&archive_path).and_then(|manifest| {
+                let target = restore_target(&manifest)?;
+                let conflicts = find_conflicts(&manifest, &target);
+                Ok((manifest, target, conflicts))
+            });
+            let _ = tx.send(RestoreMessage::Opened(result));
+        });
+    }
+
+    fn request_restore(&mut self) {
+        let Some(target) = self.target.clone() else {
+            return;
+        };
+        // 只有覆盖现有文件时才需要关心占用
+        if target.exists() && self.mode == ConflictMode::Overwrite {
+            self.process_scan = Some(process_check::ProcessCheck::start(target, "覆盖"));
+        } else {
+            self.start_restore();
+        }
+    }
+
+    fn start_restore(&mut self) {
+        let (Some(archive_path), Some(target)) = (self.archive_path.clone(), self.target.clone())
+        else {
+            return;
+        };
+
+        let (tx, rx) = mpsc::channel();
+        self.rx = Some(rx);
+        self.is_busy = true;
+        self.status_message = None;
+        self.progress = ArchiveProgress::default();
+        self.cancel_flag = Arc::new(AtomicBool::new(false));
+
+        let mode = self.mode;
+        let cancel_flag = Arc::clone(&self.cancel_flag);
+        thread::spawn(move || {
+            let progress_tx = tx.clone();
+            let result = restore_archive(&archive_path, &target, mode, &cancel_flag, |progress| {
+                let _ = progress_tx.send(RestoreMessage::Progress(progress.clone()));
+            })
+            .map(|mut report| {
+                // 部分文件被跳过或重命名时无法直接相加，在后台重新计算大小
+                report.folder_size = scanner::calculate_folder_size(&target);
+                report
+            });
+            let _ = tx.send(RestoreMessage::Finished(result));
+        });
+    }
+
+    fn poll(&mut self) {
+        let Some(rx) = &self.rx else {
+            return;
+        };
+        while let Ok(msg) = rx.try_recv() {
+            match msg {
+                RestoreMessage::Opened(result) => {
+                    self.is_busy = false;
+                    match result {
+                        Ok((manifest, target, conflicts)) => {
+                            self.status_message = None;
+                            self.manifest = Some(manifest);
+                            self.target = Some(target);
+                            self.conflicts = conflicts;
+                        }
+                        Err(err) => {
+                            logger::log_error(&err);
+                            self.status_message = Some(err);
+                        }
+                    }
+                }
+                RestoreMessage::Progress(progress) => self.progress = progress,
+                RestoreMessage::Finished(result) => {
+                    self.is_busy = false;
+                    match result {
+                        Ok(report) => {
+                            if let Some(manifest) = &self.manifest {
+                                self.restored = Some((
+                                    manifest.root.clone(),
+                                    manifest.folder_name.clone(),
+                                    report.folder_size,
+                                ));
+                            }
+                            self.status_message = Some(report.summary());
+                        }
+                        Err(err) => {
+                            logger::log_error(&err);
+                            self.status_message = Some(err);
+                        }
+                    }
+                    self.conflicts.clear();
+                }
+            }
+        }
+    }
+
+    pub fn show_restore_window(&mut self, ctx: &egui::Context) {
+        if !self.show_window {
+            return;
+        }
+        self.poll();
+        if self.is_busy {
+            ctx.request_repaint();
+        }
+
+        let mut open = true;
+        egui::Window::new("从备份还原")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("压缩包:");
+                    if let Some(path) = &self.archive_path {
+                        ui.label(path.display().to_string());
+                    }
+                    if ui
+                        .add_enabled(!self.is_busy, egui::Button::new("选择压缩包"))
+                        .clicked()
+                    {
+                        if let Ok(Some(path)) = FileDialog::new()
+                            .add_filter("备份压缩包", &["zip"])
+                            .show_open_single_file()
+                        {
+                            self.open_archive(path);
+                        }
+                    }
+                });
+
+                if let Some(manifest) = &self.manifest {
+                    egui::Grid::new("restore_manifest").show(ui, |ui| {
+                        ui.label("文件夹:");
+                        ui.label(format!("{}/{}", manifest.root, manifest.folder_name));
+                        ui.end_row();
+                        ui.label("备份时间:");
+                        ui.label(&manifest.created);
+                        ui.end_row();
+                        ui.label("内容:");
+                        ui.label(format!(
+                            "{} 个文件，共 {}",
+                            utils::format_count(manifest.files.len() as u64),
+                            utils::format_size(manifest.total_size())
+                        ));
+                        ui.end_row();
+                        ui.label("还原到:");
+                        if let Some(target) = &self.target {
+                            ui.label(target.display().to_string());
+                        }
+                        ui.end_row();
+                    });
+
+                    if !self.conflicts.is_empty() {
+                        ui.separator();
+                        ui.label(format!(
+                            "目标位置已存在 {} 个同名文件:",
+                            utils::format_count(self.conflicts.len() as u64)
+                        ));
+                        egui::ScrollArea::vertical()
+                            .max_height(150.0)
+                            .show(ui, |ui| {
+                                for path in self.conflicts.iter().take(CONFLICT_PREVIEW_LIMIT) {
+                                    ui.label(path);
+                                }
+                                if self.conflicts.len() > CONFLICT_PREVIEW_LIMIT {
+                                    ui.label(format!(
+                                        "…… 另有 {} 个文件",
+                                        self.conflicts.len() - CONFLICT_PREVIEW_LIMIT
+                                    ));
+                                }
+                            });
+                        ui.horizontal(|ui| {
+                            for mode in [
+                                ConflictMode::Skip,
+                                ConflictMode::Overwrite,
+                                ConflictMode::Rename,
+                            ] {
+                                if ui
+                                    .add_enabled(
+                                        !self.is_busy,
+                                        egui::RadioButton::new(self.mode == mode, mode.label()),
+                                    )
+                                    .clicked()
+                                {
+                                    self.mode = mode;
+                                }
+                            }
+                        });
+                    }
+                }
+
+                if self.is_busy && self.progress.bytes_total > 0 {
+                    ui.label(format!(
+                        "{}: {}",
+                        self.progress.stage, self.progress.current_path
+                    ));
+                    ui.add(egui::ProgressBar::new(self.progress.fraction()).show_percentage());
+                }
+                if let Some(message) = &self.status_message {
+                    ui.label(message);
+                }
+
+                ui.horizontal(|ui| {
+                    if self.is_busy {
+                        if self.manifest.is_some() && ui.button("取消").clicked() {
+                            self.cancel_flag.store(true, Ordering::Relaxed);
+                        }
+                    } else if self.manifest.is_some() && ui.button("开始还原").clicked() {
+                        self.request_restore();
+                    }
+                });
+            });
+
+        if let Some(scan) = &mut self.process_scan {
+            if let Some(proceed) = scan.show(ctx) {
+                self.process_scan = None;
+                if proceed {
+                    self.start_restore();
+                }
+            }
+        }
+
+        if !open {
+            self.cancel_flag.store(true, Ordering::Relaxed);
+            self.show_window = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{ManifestEntry, DATA_PREFIX, MANIFEST_NAME};
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn manifest(root: &str, folder_name: &str, original_path: &str) -> ArchiveManifest {
+        ArchiveManifest {
+            version: archive::MANIFEST_VERSION,
+            root: root.to_string(),
+            folder_name: folder_name.to_string(),
+            original_path: PathBuf::from(original_path),
+            created: String::new(),
+            directories: Vec::new(),
+            files: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restore_target_rejects_unsafe_manifests() {
+        assert_eq!(
+            restore_target(&manifest("Unknown", "App", "/data/App")).unwrap(),
+            Path::new("/data/App")
+        );
+
+        for (folder_name, original_path) in [
+            ("..", "/data/.."),
+            ("a/b", "/data/a/b"),
+            ("", "/data"),
+            ("/etc", "/etc"),
+            ("App", "relative/App"),
+            ("App", "/data/../App"),
+            ("App", "/etc/passwd"),
+        ] {
+            let manifest = manifest("Unknown", folder_name, original_path);
+            assert!(restore_target(&manifest).is_err(), "{}", folder_name);
+        }
+
+        let mut newer = manifest("Unknown", "App", "/data/App");
+        newer.version = archive::MANIFEST_VERSION + 1;
+        assert!(restore_target(&newer).is_err());
+    }
+
+    #[test]
+    fn malicious_entry_paths_are_not_extracted() {
+        let dir = std::env::temp_dir().join(format!("adc_restore_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let archive_path = dir.join("evil.zip");
+        let target = dir.join("target");
+
+        // 哈希正确、但路径指向目标目录之外的压缩包
+        let mut evil = manifest("Roaming", "App", "/data/App");
+        evil.files.push(ManifestEntry {
+            path: "../escaped.txt".to_string(),
+            size: 4,
+            sha256: utils::hash_reader(&b"evil"[..]).unwrap(),
+        });
+        let mut writer = ZipWriter::new(File::create(&archive_path).unwrap());
+        let options = SimpleFileOptions::default();
+        writer
+            .start_file(format!("{}../escaped.txt", DATA_PREFIX), options)
+            .unwrap();
+        writer.write_all(b"evil").unwrap();
+        writer.start_file(MANIFEST_NAME, options).unwrap();
+        writer
+            .write_all(serde_yaml::to_string(&evil).unwrap().as_bytes())
+            .unwrap();
+        writer.finish().unwrap();
+
+        let cancel = AtomicBool::new(false);
+        let result = restore_archive(
+            &archive_path,
+            &target,
+            ConflictMode::Overwrite,
+            &cancel,
+            |_| {},
+        );
+        assert!(result.is_err());
+        assert!(!dir.join("escaped.txt").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,607 @@
+use crate::app_dirs;
+use crate::logger;
+use crate::yaml_loader::{self, FolderDescriptions};
+use eframe::egui;
+use native_dialog::FileDialog;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, UNIX_EPOCH};
+
+pub const RULE_SOURCES_FILE: &str = "rule_sources.yaml";
+
+/// 描述规则的订阅源，`location` 为本地文件路径或 HTTP(S) 地址。
+/// 多个订阅源中有同名文件夹时，`priority` 大的生效
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleSource {
+    pub name: String,
+    pub location: String,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+pub fn load_rule_sources() -> Vec<RuleSource> {
+    let Ok(content) = fs::read_to_string(app_dirs::config_file(RULE_SOURCES_FILE)) else {
+        return Vec::new();
+    };
+    serde_yaml::from_str(&content).unwrap_or_else(|e| {
+        logger::log_error(&format!("解析规则订阅源失败: {}", e));
+        app_dirs::set_aside_unparsable(&app_dirs::config_file(RULE_SOURCES_FILE));
+        Vec::new()
+    })
+}
+
+pub fn save_rule_sources(sources: &[RuleSource]) {
+    match serde_yaml::to_string(sources) {
+        Ok(content) => {
+            if let Err(e) = fs::write(app_dirs::config_file(RULE_SOURCES_FILE), content) {
+                logger::log_error(&format!("保存规则订阅源失败: {}", e));
+            }
+        }
+        Err(e) => logger::log_error(&format!("序列化规则订阅源失败: {}", e)),
+    }
+}
+
+/// 订阅源缓存所在目录
+pub fn cache_dir() -> PathBuf {
+    app_dirs::app_dirs().cache.join("rules")
+}
+
+/// 上次下载时服务器返回的验证信息，下次请求时带上，内容未变时不必重新下载
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CacheValidators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheMeta {
+    location: String,
+    validators: CacheValidators,
+    checked_at: String,
+}
+
+pub enum Fetched {
+    NotModified,
+    Updated {
+        body: String,
+        validators: CacheValidators,
+    },
+}
+
+/// 获取订阅源内容。测试中可以换成本地文件或本地 HTTP 服务
+pub trait RuleFetcher: Send + Sync {
+    fn fetch(&self, location: &str, cached: Option<&CacheValidators>) -> Result<Fetched, String>;
+}
+
+/// 带 If-None-Match 和 If-Modified-Since 的 HTTP 请求
+pub struct HttpFetcher {
+    agent: ureq::Agent,
+}
+
+impl Default for HttpFetcher {
+    fn default() -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build(),
+        }
+    }
+}
+
+impl RuleFetcher for HttpFetcher {
+    fn fetch(&self, location: &str, cached: Option<&CacheValidators>) -> Result<Fetched, String> {
+        let mut request = self.agent.get(location);
+        if let Some(cached) = cached {
+            if let Some(etag) = &cached.etag {
+                request = request.set("If-None-Match", etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.set("If-Modified-Since", last_modified);
+            }
+        }
+        let response = request
+            .call()
+            .map_err(|e| format!("下载 {} 失败: {}", location, e))?;
+        if response.status() == 304 {
+            return Ok(Fetched::NotModified);
+        }
+
+        let validators = CacheValidators {
+            etag: response.header("ETag").map(str::to_string),
+            last_modified: response.header("Last-Modified").map(str::to_string),
+        };
+        let body = response
+            .into_string()
+            .map_err(|e| format!("读取 {} 失败: {}", location, e))?;
+        Ok(Fetched::Updated { body, validators })
+    }
+}
+
+/// 本地文件，以修改时间判断是否有变化
+pub struct FileFetcher;
+
+impl RuleFetcher for FileFetcher {
+    fn fetch(&self, location: &str, cached: Option<&CacheValidators>) -> Result<Fetched, String> {
+        let to_error = |e: std::io::Error| format!("无法读取 {}: {}", location, e);
+        let modified = fs::metadata(location)
+            .and_then(|m| m.modified())
+            .map_err(to_error)?;
+        let last_modified = modified
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos().to_string())
+            .ok();
+        if last_modified.is_some()
+            && cached.and_then(|c| c.last_modified.as_ref()) == last_modified.as_ref()
+        {
+            return Ok(Fetched::NotModified);
+        }
+        let body = fs::read_to_string(location).map_err(to_error)?;
+        Ok(Fetched::Updated {
+            body,
+            validators: CacheValidators {
+                etag: None,
+                last_modified,
+            },
+        })
+    }
+}
+
+/// 按地址选择 HTTP 或本地文件
+#[derive(Default)]
+pub struct SourceFetcher {
+    http: HttpFetcher,
+}
+
+fn is_url(location: &str) -> bool {
+    let lower = location.to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+impl RuleFetcher for SourceFetcher {
+    fn fetch(&self, location: &str, cached: Option<&CacheValidators>) -> Result<Fetched, String> {
+        if is_url(location) {
+            self.http.fetch(location, cached)
+        } else {
+            FileFetcher.fetch(location, cached)
+        }
+    }
+}
+
+// 每个订阅源的缓存文件名取地址的哈希，另存一份验证信息
+fn cache_paths(cache_dir: &Path, location: &str) -> (PathBuf, PathBuf) {
+    let hash = format!("{:x}", Sha256::digest(location.as_bytes()));
+    let stem = &hash[..16];
+    (
+        cache_dir.join(format!("{}.yaml", stem)),
+        cache_dir.join(format!("{}.meta.yaml", stem)),
+    )
+}
+
+fn load_meta(path: &Path, location: &str) -> Option<CacheMeta> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_yaml::from_str::<CacheMeta>(&content)
+        .ok()
+        .filter(|meta| meta.location == location)
+}
+
+/// 已缓存的启用订阅源内容，按优先级从低到高排列
+pub fn cached_rules(sources: &[RuleSource], cache_dir: &Path) -> Vec<(RuleSource, String)> {
+    let mut sources: Vec<&RuleSource> = sources.iter().filter(|s| s.enabled).collect();
+    sources.sort_by_key(|s| s.priority);
+    sources
+        .into_iter()
+        .filter_map(|source| {
+            let (body, _) = cache_paths(cache_dir, &source.location);
+            let content = fs::read_to_string(body).ok()?;
+            Some((source.clone(), content))
+        })
+        .collect()
+}
+
+/// 一个订阅源的检查结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceStatus {
+    Updated,
+    NotModified,
+    Failed(String),
+}
+
+impl SourceStatus {
+    pub fn label(&self) -> String {
+        match self {
+            SourceStatus::Updated => "已更新".to_string(),
+            SourceStatus::NotModified => "已是最新".to_string(),
+            SourceStatus::Failed(e) => format!("失败: {}", e),
+        }
+    }
+}
+
+/// 检查一个订阅源，内容有变化且能解析时才替换缓存
+pub fn update_source(
+    source: &RuleSource,
+    fetcher: &dyn RuleFetcher,
+    cache_dir: &Path,
+) -> Result<SourceStatus, String> {
+    let (body_path, meta_path) = cache_paths(cache_dir, &source.location);
+    // 缓存文件丢失时不能带验证信息，否则服务器会返回 304
+    let meta = load_meta(&meta_path, &source.location).filter(|_| body_path.exists());
+    let fetched = fetcher.fetch(&source.location, meta.as_ref().map(|m| &m.validators))?;
+
+    let Fetched::Updated { body, validators } = fetched else {
+        return Ok(SourceStatus::NotModified);
+    };
+    FolderDescriptions::parse(&body)?;
+    let meta = CacheMeta {
+        location: source.location.clone(),
+        validators,
+        checked_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    };
+    let to_error = |e: std::io::Error| format!("无法写入规则缓存: {}", e);
+    fs::create_dir_all(cache_dir).map_err(to_error)?;
+    fs::write(&body_path, body).map_err(to_error)?;
+    let meta = serde_yaml::to_string(&meta).map_err(|e| format!("序列化缓存信息失败: {}", e))?;
+    fs::write(&meta_path, meta).map_err(to_error)?;
+    Ok(SourceStatus::Updated)
+}
+
+/// 一次检查更新的结果
+#[derive(Debug, Default)]
+pub struct UpdateReport {
+    pub statuses: Vec<(String, SourceStatus)>, // 订阅源地址和结果
+    pub changed_entries: usize,
+}
+
+impl UpdateReport {
+    pub fn message(&self) -> String {
+        let failed = self
+            .statuses
+            .iter()
+            .filter(|(_, s)| matches!(s, SourceStatus::Failed(_)))
+            .count();
+        let mut message = if self.changed_entries > 0 {
+            format!("描述规则已更新 {} 条", self.changed_entries)
+        } else {
+            "描述规则已是最新".to_string()
+        };
+        if failed > 0 {
+            message.push_str(&format!("，{} 个订阅源检查失败", failed));
+        }
+        message
+    }
+}
+
+/// 检查所有启用的订阅源，并统计合并后的描述有多少条变化
+pub fn check_updates(
+    sources: &[RuleSource],
+    fetcher: &dyn RuleFetcher,
+    cache_dir: &Path,
+) -> UpdateReport {
+    let before = yaml_loader::merge_cached_sources(sources, cache_dir);
+    let statuses = sources
+        .iter()
+        .filter(|s| s.enabled)
+        .map(|source| {
+            let status = update_source(source, fetcher, cache_dir).unwrap_or_else(|e| {
+                logger::log_error(&format!("订阅源 {} 检查失败: {}", source.name, e));
+                SourceStatus::Failed(e)
+            });
+            (source.location.clone(), status)
+        })
+        .collect();
+    let after = yaml_loader::merge_cached_sources(sources, cache_dir);
+    UpdateReport {
+        statuses,
+        changed_entries: after.count_changes(&before),
+    }
+}
+
+/// “规则订阅”窗口，管理订阅源并在后台检查更新
+#[derive(Default)]
+pub struct RuleSourcesWindow {
+    pub show_window: bool,
+    sources: Vec<RuleSource>,
+    statuses: HashMap<String, SourceStatus>,
+    new_name: String,
+    new_location: String,
+    is_busy: bool,
+    rx: Option<Receiver<UpdateReport>>,
+    status_message: Option<String>,
+    reload: Option<String>, // 规则有变化，主界面需要重新加载描述
+}
+
+impl RuleSourcesWindow {
+    pub fn open(&mut self) {
+        self.show_window = true;
+        self.sources = load_rule_sources();
+    }
+
+    /// 在后台检查所有订阅源，没有订阅源时什么也不做
+    pub fn check_updates(&mut self) {
+        if self.is_busy {
+            return;
+        }
+        let sources = load_rule_sources();
+        if !sources.iter().any(|s| s.enabled) {
+            return;
+        }
+
+        let (tx, rx) = mpsc::channel();
+        self.rx = Some(rx);
+        self.is_busy = true;
+        self.status_message = Some("正在检查规则更新...".to_string());
+        std::thread::spawn(move || {
+            let report = check_updates(&sources, &SourceFetcher::default(), &cache_dir());
+            let _ = tx.send(report);
+        });
+    }
+
+    /// 检查完成或订阅源改变后返回要显示的消息，主界面据此重新加载描述
+    pub fn take_reload(&mut self) -> Option<String> {
+        self.reload.take()
+    }
+
+    fn poll(&mut self) {
+        let Some(rx) = &self.rx else {
+            return;
+        };
+        let Ok(report) = rx.try_recv() else {
+            return;
+        };
+        self.rx = None;
+        self.is_busy = false;
+        let message = report.message();
+        logger::log_info(&message);
+        // 没有变化时不打扰主界面，结果只显示在本窗口中
+        if report.changed_entries > 0 {
+            self.reload = Some(message.clone());
+        }
+        self.statuses = report.statuses.into_iter().collect();
+        self.status_message = Some(message);
+    }
+
+    fn save(&mut self) {
+        save_rule_sources(&self.sources);
+        self.reload = Some("规则订阅已修改".to_string());
+    }
+
+    pub fn show_rule_sources_window(&mut self, ctx: &egui::Context) {
+        self.poll();
+        if self.is_busy {
+            ctx.request_repaint_after(Duration::from_millis(200));
+        }
+        if !self.show_window {
+            return;
+        }
+
+        let mut open = true;
+        let mut changed = false;
+        let mut remove = None;
+        egui::Window::new("规则订阅")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(
+                    "同名文件夹以优先级高的订阅源为准，\
+                     配置目录中的 folders_description.yaml 优先级最高",
+                );
+
+                egui::Grid::new("rule_sources")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("名称");
+                        ui.label("位置");
+                        ui.label("优先级");
+                        ui.label("启用");
+                        ui.label("状态");
+                        ui.label("操作");
+                        ui.end_row();
+
+                        for (index, source) in self.sources.iter_mut().enumerate() {
+                            ui.label(&source.name);
+                            ui.label(&source.location);
+                            changed |= ui.add(egui::DragValue::new(&mut source.priority)).changed();
+                            changed |= ui.checkbox(&mut source.enabled, "").changed();
+                            match self.statuses.get(&source.location) {
+                                Some(status @ SourceStatus::Failed(_)) => {
+                                    ui.colored_label(egui::Color32::RED, status.label());
+                                }
+                                Some(status) => {
+                                    ui.label(status.label());
+                                }
+                                None => {
+                                    ui.label("");
+                                }
+                            }
+                            if ui.button("删除").clicked() {
+                                remove = Some(index);
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("名称:");
+                    ui.text_edit_singleline(&mut self.new_name);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("文件或网址:");
+                    ui.text_edit_singleline(&mut self.new_location);
+                    if ui.button("浏览").clicked() {
+                        if let Ok(Some(path)) = FileDialog::new()
+                            .add_filter("描述规则", &["yaml", "yml"])
+                            .show_open_single_file()
+                        {
+                            self.new_location = path.display().to_string();
+                        }
+                    }
+                });
+                let location = self.new_location.trim().to_string();
+                if ui
+                    .add_enabled(!location.is_empty(), egui::Button::new("添加订阅源"))
+                    .clicked()
+                {
+                    let name = match self.new_name.trim() {
+                        "" => location.clone(),
+                        name => name.to_string(),
+                    };
+                    self.sources.retain(|s| s.location != location);
+                    self.sources.push(RuleSource {
+                        name,
+                        location,
+                        priority: 0,
+                        enabled: true,
+                    });
+                    self.new_name.clear();
+                    self.new_location.clear();
+                    changed = true;
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(!self.is_busy, egui::Button::new("检查更新"))
+                        .clicked()
+                    {
+                        self.check_updates();
+                    }
+                    if self.is_busy {
+                        ui.spinner();
+                    }
+                    if let Some(message) = &self.status_message {
+                        ui.label(message);
+                    }
+                });
+            });
+        if !open {
+            self.show_window = false;
+        }
+
+        if let Some(index) = remove {
+            let source = self.sources.remove(index);
+            logger::log_info(&format!("已删除规则订阅源: {}", source.location));
+            changed = true;
+        }
+        if changed {
+            self.save();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    // 本地 HTTP 服务：带着正确的 ETag 请求时返回 304，否则返回规则内容
+    fn serve(body: &'static str, requests: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}/rules.yaml", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut revalidated = false;
+                for line in BufReader::new(&stream).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    revalidated |= line.eq_ignore_ascii_case("if-none-match: \"v1\"");
+                }
+                let response = if revalidated {
+                    "HTTP/1.1 304 Not Modified\r\nContent-Length: 0\r\n\r\n".to_string()
+                } else {
+                    format!(
+                        "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        address
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("adc_rules_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn http_source_is_cached_and_revalidated_with_etag() {
+        let cache = temp_dir("http");
+        let source = RuleSource {
+            name: "remote".to_string(),
+            location: serve("Roaming:\n  Code: 编辑器\n", 2),
+            priority: 0,
+            enabled: true,
+        };
+        let fetcher = SourceFetcher::default();
+
+        let first = check_updates(std::slice::from_ref(&source), &fetcher, &cache);
+        let second = check_updates(std::slice::from_ref(&source), &fetcher, &cache);
+
+        assert_eq!(first.statuses[0].1, SourceStatus::Updated);
+        assert_eq!(first.changed_entries, 1);
+        assert_eq!(second.statuses[0].1, SourceStatus::NotModified);
+        assert_eq!(second.changed_entries, 0);
+        let merged = yaml_loader::merge_cached_sources(&[source], &cache);
+        assert_eq!(
+            merged.get_description("Code", "Roaming").as_deref(),
+            Some("编辑器")
+        );
+        fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[test]
+    fn higher_priority_source_wins_and_invalid_content_keeps_cache() {
+        let dir = temp_dir("priority");
+        let cache = dir.join("cache");
+        let (low, high) = (dir.join("low.yaml"), dir.join("high.yaml"));
+        fs::write(&low, "Local:\n  Temp: 低\n  Cache: 缓存\n").unwrap();
+        fs::write(&high, "Local:\n  Temp: 高\n").unwrap();
+        let source = |path: &Path, priority| RuleSource {
+            name: path.display().to_string(),
+            location: path.display().to_string(),
+            priority,
+            enabled: true,
+        };
+        let sources = [source(&high, 10), source(&low, 1)];
+
+        let report = check_updates(&sources, &SourceFetcher::default(), &cache);
+        assert_eq!(report.changed_entries, 2);
+        let merged = yaml_loader::merge_cached_sources(&sources, &cache);
+        assert_eq!(
+            merged.get_description("Temp", "Local").as_deref(),
+            Some("高")
+        );
+        assert_eq!(
+            merged.get_description("Cache", "Local").as_deref(),
+            Some("缓存")
+        );
+
+        fs::write(&high, "Local: [无效").unwrap();
+        let report = check_updates(&sources, &SourceFetcher::default(), &cache);
+        assert!(matches!(report.statuses[0].1, SourceStatus::Failed(_)));
+        let merged = yaml_loader::merge_cached_sources(&sources, &cache);
+        assert_eq!(
+            merged.get_description("Temp", "Local").as_deref(),
+            Some("高")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -24,7 +24,8 @@ pub fn scan_appdata(tx: Sender<(String, u64)>, folder_type: &str) {
         thread::spawn(move || {
             if let Ok(entries) = fs::read_dir(&appdata_dir) {
                 for entry in entries.flatten() {
-                    if let Ok(metadata) = entry.metadata() {
+                    // 跟随链接，移动后留下的链接也作为文件夹列出
+                    if let Ok(metadata) = fs::metadata(entry.path()) {
                         if metadata.is_dir() {
                             let folder_name = entry.file_name().to_string_lossy().to_string();
                             let size = calculate_folder_size(&entry.path());
@@ -40,8 +41,16 @@ pub fn scan_appdata(tx: Sender<(String, u64)>, folder_type: &str) {
     }
 }
 
+/// 文件夹是链接时返回它指向的位置，用于识别已移动的文件夹
+pub fn link_target(folder: &Path) -> Option<PathBuf> {
+    if !crate::move_journal::is_link(folder) {
+        return None;
+    }
+    fs::read_link(folder).ok()
+}
+
 // 计算文件夹的总大小（递归）
-fn calculate_folder_size(folder: &Path) -> u64 {
+pub fn calculate_folder_size(folder: &Path) -> u64 {
     let mut size = 0;
 
     // 遍历文件夹中的所有条目
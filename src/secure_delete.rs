@@ -0,0 +1,195 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// 每次写入的块大小
+const WIPE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// `wipe_file` 的结果，`path` 为需要由调用方删除的路径
+pub struct Wiped {
+    pub path: PathBuf,
+    pub overwritten: bool, // 内容是否已被覆盖
+}
+
+/// 覆盖文件内容后截断并重命名为随机名称，由调用方负责删除。
+/// 符号链接不会被跟随；有其他硬链接的文件覆盖后会破坏其他位置的同一内容，
+/// 这两种情况都不覆盖，只返回原路径
+pub fn wipe_file(path: &Path) -> io::Result<Wiped> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_file() || has_other_links(&metadata) {
+        return Ok(Wiped {
+            path: path.to_path_buf(),
+            overwritten: false,
+        });
+    }
+
+    // 只读文件需要先去掉只读属性才能覆盖
+    let mut permissions = metadata.permissions();
+    if permissions.readonly() {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            permissions.set_mode(permissions.mode() | 0o200);
+        }
+        #[cfg(not(unix))]
+        permissions.set_readonly(false);
+        fs::set_permissions(path, permissions)?;
+    }
+
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let mut rng = random_seed(path);
+    let mut buffer = vec![0u8; WIPE_BUFFER_SIZE];
+    let mut remaining = metadata.len();
+    while remaining > 0 {
+        let chunk = remaining.min(WIPE_BUFFER_SIZE as u64) as usize;
+        fill_random(&mut buffer[..chunk], &mut rng);
+        file.write_all(&buffer[..chunk])?;
+        remaining -= chunk as u64;
+    }
+    file.sync_all()?;
+
+    // 截断为 0 字节，避免文件大小泄露信息
+    file.set_len(0)?;
+    file.sync_all()?;
+    drop(file);
+
+    // 重命名为随机名称，避免原文件名残留在目录项中
+    let renamed = path.with_file_name(format!("{:016x}.wipe", next_random(&mut rng)));
+    fs::rename(path, &renamed)?;
+    Ok(Wiped {
+        path: renamed,
+        overwritten: true,
+    })
+}
+
+#[cfg(unix)]
+fn has_other_links(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    metadata.nlink() > 1
+}
+
+// Windows 的硬链接数需要打开文件句柄查询，暂不检测
+#[cfg(not(unix))]
+fn has_other_links(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// 检查目标所在的存储设备，列出覆盖写入无法保证彻底清除的情况
+pub fn storage_caveats(path: &Path) -> Vec<String> {
+    let mut caveats = platform_caveats(path);
+    caveats.push("文件的旧副本（备份、同步盘、系统还原点）不会被覆盖".to_string());
+    caveats
+}
+
+#[cfg(target_os = "linux")]
+fn platform_caveats(path: &Path) -> Vec<String> {
+    let mut caveats = Vec::new();
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    // 在 mountinfo 中找到包含该路径的最长挂载点
+    let Ok(mountinfo) = fs::read_to_string("/proc/self/mountinfo") else {
+        caveats.push("无法确认文件系统类型，覆盖写入可能无法保证彻底清除".to_string());
+        return caveats;
+    };
+    let mount = mountinfo
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(' ').collect();
+            let separator = fields.iter().position(|&f| f == "-")?;
+            let mount_point = *fields.get(4)?;
+            let device = *fields.get(2)?;
+            let fs_type = *fields.get(separator + 1)?;
+            Some((mount_point, device, fs_type))
+        })
+        .filter(|(mount_point, _, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _, _)| mount_point.len());
+
+    let Some((_, device, fs_type)) = mount else {
+        return caveats;
+    };
+
+    if matches!(fs_type, "btrfs" | "zfs" | "bcachefs" | "f2fs" | "nilfs2") {
+        caveats.push(format!(
+            "{} 是写时复制或日志结构文件系统，覆盖的数据会写到新位置，旧内容可能仍在磁盘上",
+            fs_type
+        ));
+    }
+
+    // 分区的 queue 目录在上一级设备目录中
+    let sys_dev = Path::new("/sys/dev/block").join(device);
+    let rotational = fs::read_to_string(sys_dev.join("queue/rotational"))
+        .or_else(|_| fs::read_to_string(sys_dev.join("../queue/rotational")));
+    match rotational.as_deref().map(str::trim) {
+        Ok("0") => caveats
+            .push("目标位于固态硬盘，磨损均衡可能保留旧数据，覆盖写入无法保证彻底清除".to_string()),
+        Ok(_) => {}
+        Err(_) => caveats.push("无法确认存储设备类型，若为固态硬盘则无法保证彻底清除".to_string()),
+    }
+
+    caveats
+}
+
+#[cfg(not(target_os = "linux"))]
+fn platform_caveats(_path: &Path) -> Vec<String> {
+    vec![
+        "无法确认存储设备类型，若为固态硬盘，磨损均衡可能保留旧数据".to_string(),
+        "NTFS 中的小文件可能直接存放在主文件表中，覆盖后仍可能残留".to_string(),
+    ]
+}
+
+// 简单的 xorshift 伪随机数，只用于生成覆盖数据和文件名
+fn random_seed(path: &Path) -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    let path_hash = path
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, b| {
+            (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+    (nanos ^ path_hash) | 1
+}
+
+fn next_random(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+fn fill_random(buffer: &mut [u8], state: &mut u64) {
+    for chunk in buffer.chunks_mut(8) {
+        let bytes = next_random(state).to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hard_linked_files_are_not_overwritten() {
+        let dir = std::env::temp_dir().join(format!("adc_secure_delete_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("data.txt");
+        let other_link = dir.join("other.txt");
+        fs::write(&file, "keep me").unwrap();
+        fs::hard_link(&file, &other_link).unwrap();
+
+        let wiped = wipe_file(&file).unwrap();
+        assert!(!wiped.overwritten);
+        assert_eq!(wiped.path, file);
+        assert_eq!(fs::read_to_string(&other_link).unwrap(), "keep me");
+
+        fs::remove_file(&file).unwrap();
+        let wiped = wipe_file(&other_link).unwrap();
+        assert!(wiped.overwritten);
+        assert_eq!(fs::metadata(&wiped.path).unwrap().len(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,234 @@
+use crate::app_dirs;
+use crate::logger;
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+pub const SETTINGS_FILE: &str = "settings.yaml";
+
+// 修改字段含义时加一，并在 migrate 中转换旧版本
+const SETTINGS_VERSION: u32 = 1;
+
+/// 保存在配置目录中的界面和行为设置，缺少的字段使用默认值
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    pub selected_root: String,          // 启动时显示的根目录
+    pub sort_criterion: Option<String>, // "name" 或 "size"
+    pub sort_order: Option<String>,     // "asc" 或 "desc"
+    pub logging_enabled: bool,
+    pub hide_ignored: bool,
+    pub window_size: [f32; 2],
+    pub large_folder_mb: u64, // 超过该大小的文件夹突出显示，0 表示不启用
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            selected_root: "Roaming".to_string(),
+            sort_criterion: None,
+            sort_order: None,
+            logging_enabled: false,
+            hide_ignored: false,
+            window_size: [800.0, 600.0],
+            large_folder_mb: 1024,
+        }
+    }
+}
+
+impl Settings {
+    // 版本 0 是加入版本号之前的文件，字段与版本 1 相同，只需补上版本号。
+    // 以后修改字段时在这里按旧版本号逐步转换
+    fn migrate(mut self) -> Self {
+        self.version = SETTINGS_VERSION;
+        self
+    }
+
+    /// 文件夹大小是否超过阈值
+    pub fn is_large(&self, size: u64) -> bool {
+        self.large_folder_mb > 0 && size >= self.large_folder_mb.saturating_mul(1024 * 1024)
+    }
+}
+
+pub fn load_settings() -> Settings {
+    let Ok(content) = fs::read_to_string(app_dirs::config_file(SETTINGS_FILE)) else {
+        return Settings::default();
+    };
+    let value: serde_yaml::Value = match serde_yaml::from_str(&content) {
+        Ok(value) => value,
+        Err(e) => {
+            logger::log_error(&format!("解析设置失败: {}", e));
+            return Settings::default();
+        }
+    };
+    let version = value
+        .get("version")
+        .and_then(serde_yaml::Value::as_u64)
+        .unwrap_or(0) as u32;
+    let settings: Settings = serde_yaml::from_value(value).unwrap_or_else(|e| {
+        logger::log_error(&format!("解析设置失败: {}", e));
+        Settings::default()
+    });
+
+    if version > SETTINGS_VERSION {
+        logger::log_error(&format!(
+            "设置文件来自更新的版本 ({})，未识别的设置将被忽略",
+            version
+        ));
+        return settings;
+    }
+    if version < SETTINGS_VERSION {
+        let settings = settings.migrate();
+        save_settings(&settings);
+        logger::log_info(&format!(
+            "设置已从版本 {} 升级到 {}",
+            version, SETTINGS_VERSION
+        ));
+        return settings;
+    }
+    settings
+}
+
+pub fn save_settings(settings: &Settings) {
+    match serde_yaml::to_string(settings) {
+        Ok(content) => {
+            if let Err(e) = fs::write(app_dirs::config_file(SETTINGS_FILE), content) {
+                logger::log_error(&format!("保存设置失败: {}", e));
+            }
+        }
+        Err(e) => logger::log_error(&format!("序列化设置失败: {}", e)),
+    }
+}
+
+/// “设置”窗口，编辑副本，点击“应用”后交给主界面
+#[derive(Default)]
+pub struct SettingsWindow {
+    pub show_window: bool,
+    draft: Settings,
+    applied: Option<Settings>,
+}
+
+impl SettingsWindow {
+    pub fn open(&mut self, current: &Settings) {
+        self.show_window = true;
+        self.draft = current.clone();
+    }
+
+    pub fn take_applied(&mut self) -> Option<Settings> {
+        self.applied.take()
+    }
+
+    pub fn show_settings_window(&mut self, ctx: &egui::Context) {
+        if !self.show_window {
+            return;
+        }
+
+        let mut open = true;
+        egui::Window::new("设置")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("settings").num_columns(2).show(ui, |ui| {
+                    ui.label("根目录:");
+                    egui::ComboBox::from_id_salt("settings_root")
+                        .selected_text(&self.draft.selected_root)
+                        .show_ui(ui, |ui| {
+                            for root in ["Roaming", "Local", "LocalLow"] {
+                                ui.selectable_value(
+                                    &mut self.draft.selected_root,
+                                    root.to_string(),
+                                    root,
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("排序:");
+                    let label = match (
+                        self.draft.sort_criterion.as_deref(),
+                        self.draft.sort_order.as_deref(),
+                    ) {
+                        (Some("name"), Some("asc")) => "名称正序",
+                        (Some("size"), Some("asc")) => "大小正序",
+                        (Some("name"), Some("desc")) => "名称倒序",
+                        (Some("size"), Some("desc")) => "大小倒序",
+                        _ => "不排序",
+                    };
+                    egui::ComboBox::from_id_salt("settings_sort")
+                        .selected_text(label)
+                        .show_ui(ui, |ui| {
+                            for (text, criterion, order) in [
+                                ("不排序", None, None),
+                                ("名称正序", Some("name"), Some("asc")),
+                                ("大小正序", Some("size"), Some("asc")),
+                                ("名称倒序", Some("name"), Some("desc")),
+                                ("大小倒序", Some("size"), Some("desc")),
+                            ] {
+                                if ui.selectable_label(label == text, text).clicked() {
+                                    self.draft.sort_criterion = criterion.map(str::to_string);
+                                    self.draft.sort_order = order.map(str::to_string);
+                                }
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("大文件夹阈值:");
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::DragValue::new(&mut self.draft.large_folder_mb)
+                                .range(0..=1024 * 1024)
+                                .suffix(" MB"),
+                        );
+                        ui.label("（0 表示不突出显示）");
+                    });
+                    ui.end_row();
+                });
+
+                ui.checkbox(&mut self.draft.logging_enabled, "启用日志");
+                ui.checkbox(&mut self.draft.hide_ignored, "隐藏被忽略的文件夹");
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("应用").clicked() {
+                        self.applied = Some(self.draft.clone());
+                    }
+                    if ui.button("恢复默认").clicked() {
+                        // 窗口大小不在这里设置，保持当前值
+                        self.draft = Settings {
+                            window_size: self.draft.window_size,
+                            ..Settings::default()
+                        };
+                    }
+                });
+                ui.label(format!(
+                    "设置保存在 {}",
+                    app_dirs::config_file(SETTINGS_FILE).display()
+                ));
+            });
+        if !open {
+            self.show_window = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unversioned_settings_are_migrated_and_missing_fields_defaulted() {
+        let path = app_dirs::config_file(SETTINGS_FILE);
+        fs::write(&path, "selected_root: Local\nhide_ignored: true\n").unwrap();
+
+        let settings = load_settings();
+
+        assert_eq!(settings.selected_root, "Local");
+        assert!(settings.hide_ignored);
+        assert_eq!(settings.window_size, Settings::default().window_size);
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert!(fs::read_to_string(&path).unwrap().contains("version: 1"));
+    }
+}
@@ -1,30 +1,60 @@
 use crate::about;
+use crate::age_cleanup;
+use crate::app_dirs;
+use crate::archive;
 use crate::confirmation;
+use crate::content_cleaner;
 use crate::delete;
 use crate::ignore;
 use crate::logger; // 导入 logger 模块
+use crate::move_journal;
 use crate::move_module; // 导入移动模块
 use crate::open;
+use crate::process_check;
+use crate::relocation;
+use crate::restore;
+use crate::rule_sources;
 use crate::scanner;
+use crate::settings;
 use crate::utils;
-use crate::yaml_loader::{load_folder_descriptions, FolderDescriptions};
+use crate::yaml_loader::{load_folder_descriptions, FolderDescriptions, DESCRIPTIONS_FILE};
 use eframe::egui::{self, Grid, ScrollArea};
-use std::collections::HashSet;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::mpsc::{Receiver, Sender};
 
+// 移动后留下的链接，`move_back` 为移动记录的检查结果，记录不一致时不能移回
+struct LinkedFolder {
+    target: PathBuf,
+    move_back: Result<(), String>,
+}
+
 pub struct AppDataCleaner {
     is_scanning: bool,
-    current_folder: Option<String>,
     folder_data: Vec<(String, u64)>,
     show_about_window: bool,                // 确保字段存在
-    confirm_delete: Option<(String, bool)>, // 保存要确认删除的文件夹状态
+    confirm_delete: Option<confirmation::DeleteRequest>, // 等待确认的删除请求
+    delete_task: Option<delete::DeleteTask>, // 正在后台进行的删除任务
+    process_scan: Option<process_check::ProcessCheck>, // 检查是否有进程正在使用待删除的文件夹
     selected_appdata_folder: String,        // 新增字段
     tx: Option<Sender<(String, u64)>>,
     rx: Option<Receiver<(String, u64)>>,
     is_logging_enabled: bool,             // 控制日志是否启用
     previous_logging_state: bool,         // 记录上一次日志启用状态
-    ignored_folders: HashSet<String>,     // 忽略文件夹集合
+    ignore_list: ignore::IgnoreList,      // 忽略规则
+    ignore_window: ignore::IgnoreWindow,  // 忽略列表窗口
+    hide_ignored: bool,                   // 不显示被忽略的文件夹
+    linked_folders: HashMap<String, LinkedFolder>, // 已移动的文件夹及其链接指向的位置
     move_module: move_module::MoveModule, // 移动模块实例
+    content_cleaner: content_cleaner::ContentCleaner, // 清理内容窗口
+    age_cleanup: age_cleanup::AgeCleanup, // 按时间清理窗口
+    archive_module: archive::ArchiveModule, // 备份后删除窗口
+    restore_module: restore::RestoreModule, // 从备份还原窗口
+    relocation_window: relocation::RelocationWindow, // 已移动的文件夹窗口
+    settings: settings::Settings,   // 上次保存的设置
+    window_size: Option<[f32; 2]>,  // 当前窗口大小，退出时保存
+    rule_sources_window: rule_sources::RuleSourcesWindow, // 规则订阅窗口
+    settings_window: settings::SettingsWindow, // 设置窗口
     folder_descriptions: Option<FolderDescriptions>,
     yaml_error_logged: bool,        // 新增字段，用于标记是否已经记录过错误
     status: Option<String>,         // 添加 status 字段
@@ -38,20 +68,36 @@ impl Default for AppDataCleaner {
         let (tx, rx) = std::sync::mpsc::channel();
         Self {
             is_scanning: false,
-            current_folder: None,
             folder_data: vec![],
             show_about_window: false,                       // 默认值
             confirm_delete: None,                           // 初始化为 None
+            delete_task: None,                              // 初始时没有删除任务
+            process_scan: None,
             selected_appdata_folder: "Roaming".to_string(), // 默认值为 Roaming
             tx: Some(tx),
             rx: Some(rx),
             is_logging_enabled: false,     // 默认禁用日志
             previous_logging_state: false, // 初始时假定日志系统未启用
-            ignored_folders: ignore::load_ignored_folders(),
+            ignore_list: ignore::load_ignore_list(),
+            ignore_window: Default::default(),
+            hide_ignored: false,
+            linked_folders: HashMap::new(),
             move_module: Default::default(),
+            content_cleaner: Default::default(),
+            age_cleanup: Default::default(),
+            archive_module: Default::default(),
+            restore_module: Default::default(),
+            relocation_window: Default::default(),
+            settings: Default::default(),
+            window_size: None,
+            rule_sources_window: Default::default(),
+            settings_window: Default::default(),
             folder_descriptions: None,
             yaml_error_logged: false,           // 初始时假定未记录过错误
-            status: Some("未扫描".to_string()), // 初始化为 "未扫描"
+            // 上次移动中途退出时先恢复，再显示结果
+            status: Some(
+                move_journal::recover_interrupted_move().unwrap_or_else(|| "未扫描".to_string()),
+            ),
             sort_criterion: None,               // 初始化为 None
             sort_order: None,                   // 初始化为 None
             total_size: 0,                      // 初始化为 0
@@ -60,6 +106,15 @@ impl Default for AppDataCleaner {
 }
 
 impl AppDataCleaner {
+    /// 按保存的设置恢复上次的界面状态
+    pub fn new(settings: settings::Settings) -> Self {
+        let mut app = Self::default();
+        app.apply_settings(&settings);
+        app.settings = settings;
+        app.rule_sources_window.check_updates(); // 启动时在后台检查订阅的描述规则
+        app
+    }
+
     fn setup_custom_fonts(&self, ctx: &egui::Context) {
         use eframe::egui::{FontData, FontDefinitions};
 
@@ -80,6 +135,66 @@ impl AppDataCleaner {
 
         ctx.set_fonts(fonts);
     }
+
+    fn switch_root(&mut self, root: &str) {
+        self.selected_appdata_folder = root.to_string();
+        self.folder_data.clear();
+        self.linked_folders.clear();
+        self.is_scanning = false;
+        self.status = Some("未扫描".to_string()); // 更新状态为 "未扫描"
+    }
+
+    fn apply_settings(&mut self, settings: &settings::Settings) {
+        if settings.selected_root != self.selected_appdata_folder {
+            self.switch_root(&settings.selected_root);
+        }
+        self.sort_criterion = settings.sort_criterion.clone();
+        self.sort_order = settings.sort_order.clone();
+        self.is_logging_enabled = settings.logging_enabled;
+        self.hide_ignored = settings.hide_ignored;
+    }
+
+    // 当前界面状态对应的设置，与上次保存的不同时写入文件。
+    // 调整窗口大小时每一帧都会变化，窗口大小只在退出时保存
+    fn save_settings_if_changed(&mut self, ctx: &egui::Context) {
+        if let Some(rect) = ctx.input(|i| i.viewport().inner_rect) {
+            self.window_size = Some([rect.width(), rect.height()]);
+        }
+        let current = settings::Settings {
+            selected_root: self.selected_appdata_folder.clone(),
+            sort_criterion: self.sort_criterion.clone(),
+            sort_order: self.sort_order.clone(),
+            logging_enabled: self.is_logging_enabled,
+            hide_ignored: self.hide_ignored,
+            ..self.settings.clone()
+        };
+        if current != self.settings {
+            settings::save_settings(&current);
+            self.settings = current;
+        }
+    }
+
+    // 文件夹是移动后留下的链接时记录它指向的位置
+    fn update_link_state(&mut self, folder: &str) {
+        let Some(source) =
+            utils::get_appdata_dir(&self.selected_appdata_folder).map(|base| base.join(folder))
+        else {
+            self.linked_folders.remove(folder);
+            return;
+        };
+        match scanner::link_target(&source) {
+            Some(target) => {
+                let move_back =
+                    relocation::move_back_target(&relocation::load_relocations(), &source)
+                        .map(|_| ());
+                self.linked_folders
+                    .insert(folder.to_string(), LinkedFolder { target, move_back });
+            }
+            None => {
+                self.linked_folders.remove(folder);
+            }
+        }
+    }
 }
 
 impl eframe::App for AppDataCleaner {
@@ -88,8 +203,10 @@ impl eframe::App for AppDataCleaner {
 
         // 加载描述文件
         if self.folder_descriptions.is_none() {
-            self.folder_descriptions =
-                load_folder_descriptions("folders_description.yaml", &mut self.yaml_error_logged);
+            self.folder_descriptions = load_folder_descriptions(
+                &app_dirs::config_file(DESCRIPTIONS_FILE),
+                &mut self.yaml_error_logged,
+            );
         }
 
         if self.is_logging_enabled != self.previous_logging_state {
@@ -106,6 +223,8 @@ impl eframe::App for AppDataCleaner {
         confirmation::handle_delete_confirmation(
             ctx,
             &mut self.confirm_delete,
+            &mut self.delete_task,
+            &mut self.process_scan,
             &self.selected_appdata_folder,
             &mut self.status,
             &mut self.folder_data,
@@ -118,16 +237,49 @@ impl eframe::App for AppDataCleaner {
                 ui.close_menu();
             }
 
+            if ui.button("按时间清理").clicked() {
+                self.age_cleanup.open();
+            }
+
+            if ui.button("从备份还原").clicked() {
+                self.restore_module.open();
+            }
+
+            if ui.button("已移动的文件夹").clicked() {
+                self.relocation_window.open();
+            }
+
+            if ui.button("忽略列表").clicked() {
+                self.ignore_window.open();
+            }
+
+            let dirs = app_dirs::app_dirs();
+            let hover = if dirs.portable {
+                format!("便携模式: {}", dirs.config.display())
+            } else {
+                dirs.config.display().to_string()
+            };
+            if ui.button("打开配置目录").on_hover_text(hover).clicked() {
+                if let Err(err) = open::open_folder(&dirs.config) {
+                    logger::log_error(&format!("无法打开配置目录: {}", err));
+                }
+            }
+
+            if ui.button("规则订阅").clicked() {
+                self.rule_sources_window.open();
+            }
+
+            if ui.button("设置").clicked() {
+                self.settings_window.open(&self.settings);
+            }
+
             ui.separator();
             ui.checkbox(&mut self.is_logging_enabled, "启用日志");
 
             ui.menu_button("切换文件夹", |ui| {
                 for folder in ["Roaming", "Local", "LocalLow"] {
                     if ui.button(folder).clicked() {
-                        self.selected_appdata_folder = folder.to_string();
-                        self.folder_data.clear();
-                        self.is_scanning = false;
-                        self.status = Some("未扫描".to_string()); // 更新状态为 "未扫描"
+                        self.switch_root(folder);
                         ui.close_menu();
                     }
                 }
@@ -139,6 +291,7 @@ impl eframe::App for AppDataCleaner {
             if ui.button("立即扫描").clicked() && !self.is_scanning {
                 self.is_scanning = true;
                 self.folder_data.clear();
+                self.linked_folders.clear();
                 self.status = Some("扫描中...".to_string()); // 更新状态为 "扫描中..."
 
                 let tx = self.tx.clone().unwrap();
@@ -147,15 +300,14 @@ impl eframe::App for AppDataCleaner {
                 scanner::scan_appdata(tx, &folder_type);
             }
 
-            if let Some(rx) = &self.rx {
-                while let Ok((folder, size)) = rx.try_recv() {
-                    // 检查是否接收到扫描完成标志
-                    if folder == "__SCAN_COMPLETE__" {
-                        self.is_scanning = false;
-                        self.status = Some("扫描完成".to_string()); // 更新状态为 "扫描完成"
-                    } else {
-                        self.folder_data.push((folder, size));
-                    }
+            while let Some(Ok((folder, size))) = self.rx.as_ref().map(|rx| rx.try_recv()) {
+                // 检查是否接收到扫描完成标志
+                if folder == "__SCAN_COMPLETE__" {
+                    self.is_scanning = false;
+                    self.status = Some("扫描完成".to_string()); // 更新状态为 "扫描完成"
+                } else {
+                    self.update_link_state(&folder);
+                    self.folder_data.push((folder, size));
                 }
             }
 
@@ -184,12 +336,15 @@ impl eframe::App for AppDataCleaner {
                 }
             });
 
+            ui.checkbox(&mut self.hide_ignored, "隐藏被忽略的文件夹");
+
             // 计算总大小
             self.total_size = self.folder_data.iter().map(|(_, size)| size).sum();
 
             // 显示总大小
             ui.label(format!("总大小: {}", utils::format_size(self.total_size)));
 
+            let mut ignore_request = None;
             ScrollArea::vertical().show(ui, |ui| {
                 Grid::new("folders_table").striped(true).show(ui, |ui| {
                     ui.label("文件夹");
@@ -217,17 +372,42 @@ impl eframe::App for AppDataCleaner {
                     }
 
                     for (folder, size) in &self.folder_data {
-                        if self.ignored_folders.contains(folder) {
+                        let ignore_rule = self
+                            .ignore_list
+                            .matching_rule(&self.selected_appdata_folder, folder);
+                        if ignore_rule.is_some() && self.hide_ignored {
+                            continue;
+                        }
+                        if let Some(rule) = ignore_rule {
                             ui.add_enabled(
                                 false,
                                 egui::Label::new(
                                     egui::RichText::new(folder).color(egui::Color32::GRAY),
                                 ),
+                            )
+                            .on_disabled_hover_text(
+                                match &rule.entry.reason {
+                                    Some(reason) => {
+                                        format!("被规则 {} 忽略: {}", rule.entry.pattern, reason)
+                                    }
+                                    None => format!("被规则 {} 忽略", rule.entry.pattern),
+                                },
                             );
                         } else {
                             ui.label(folder);
                         }
-                        ui.label(utils::format_size(*size));
+                        if let Some(linked) = self.linked_folders.get(folder) {
+                            ui.label(format!("{}（已移动）", utils::format_size(*size)))
+                                .on_hover_text(format!("链接指向 {}", linked.target.display()));
+                        } else if self.settings.is_large(*size) {
+                            ui.colored_label(
+                                egui::Color32::from_rgb(220, 120, 0),
+                                utils::format_size(*size),
+                            )
+                            .on_hover_text("超过设置中的大文件夹阈值");
+                        } else {
+                            ui.label(utils::format_size(*size));
+                        }
 
                         // 读取描述信息并显示
                         let description = self.folder_descriptions.as_ref().and_then(|desc| {
@@ -239,26 +419,46 @@ impl eframe::App for AppDataCleaner {
                             ui.label("无描述");
                         }
 
-                        if !self.ignored_folders.contains(folder) {
+                        if ignore_rule.is_none() {
                             if ui.button("彻底删除").clicked() {
-                                self.confirm_delete = Some((folder.clone(), false));
+                                self.confirm_delete =
+                                    Some(confirmation::DeleteRequest::new(folder.clone()));
                                 self.status = None; // 每次点击"彻底删除"时清除状态
                             }
-                            if ui.button("移动").clicked() {
-                                self.move_module.show_window = true;
-                                self.move_module.folder_name = folder.clone();
+                            if ui.button("备份后删除").clicked() {
+                                self.archive_module
+                                    .open(&self.selected_appdata_folder, folder);
+                            }
+                            if ui.button("清理内容").clicked() {
+                                self.content_cleaner
+                                    .open(&self.selected_appdata_folder, folder);
+                            }
+                            if let Some(linked) = self.linked_folders.get(folder) {
+                                let button = ui.add_enabled(
+                                    linked.move_back.is_ok(),
+                                    egui::Button::new("移回"),
+                                );
+                                if let Err(reason) = &linked.move_back {
+                                    button.on_disabled_hover_text(reason);
+                                } else if button.clicked() {
+                                    self.move_module
+                                        .open_move_back(&self.selected_appdata_folder, folder);
+                                }
+                            } else if ui.button("移动").clicked() {
+                                self.move_module.open(&self.selected_appdata_folder, folder);
                             }
                             if ui.button("忽略").clicked() {
-                                self.ignored_folders.insert(folder.clone());
-                                ignore::save_ignored_folders(&self.ignored_folders);
-                                logger::log_info(&format!("文件夹 '{}' 已被忽略", folder));
+                                ignore_request = Some(folder.clone());
                             }
                         } else {
                             ui.add_enabled(false, |ui: &mut egui::Ui| {
                                 let response1 = ui.button("彻底删除");
-                                let response2 = ui.button("移动");
-                                let response3 = ui.button("忽略");
-                                response1 | response2 | response3 // 返回合并的 Response
+                                let response2 = ui.button("备份后删除");
+                                let response3 = ui.button("清理内容");
+                                let response4 = ui.button("移动");
+                                let response5 = ui.button("忽略");
+                                // 返回合并的 Response
+                                response1 | response2 | response3 | response4 | response5
                             });
                         }
                         if ui.button("打开").clicked() {
@@ -275,6 +475,16 @@ impl eframe::App for AppDataCleaner {
                     }
                 });
             });
+
+            // 遍历列表时规则被借用，点击“忽略”后再写入
+            if let Some(folder) = ignore_request {
+                self.ignore_list.add(ignore::IgnoreEntry::new(
+                    &self.selected_appdata_folder,
+                    &folder,
+                ));
+                ignore::save_ignore_list(&self.ignore_list);
+                logger::log_info(&format!("文件夹 '{}' 已被忽略", folder));
+            }
         });
 
         // 关于窗口
@@ -282,7 +492,81 @@ impl eframe::App for AppDataCleaner {
             about::show_about_window(ctx, &mut self.show_about_window);
         }
 
-        // 显示移动窗口
+        // 显示移动窗口，完成后更新文件夹的链接状态
         self.move_module.show_move_window(ctx);
+        if let Some(folder) = self.move_module.take_completed() {
+            if self.move_module.root == self.selected_appdata_folder {
+                self.update_link_state(&folder);
+            }
+        }
+
+        // 显示规则订阅窗口，规则有变化时重新加载描述
+        self.rule_sources_window.show_rule_sources_window(ctx);
+        if let Some(message) = self.rule_sources_window.take_reload() {
+            self.folder_descriptions = None;
+            self.yaml_error_logged = false;
+            self.status = Some(message);
+        }
+
+        // 显示设置窗口，应用后立即生效并保存
+        self.settings_window.show_settings_window(ctx);
+        if let Some(settings) = self.settings_window.take_applied() {
+            self.apply_settings(&settings);
+            self.settings.large_folder_mb = settings.large_folder_mb;
+        }
+        self.save_settings_if_changed(ctx);
+
+        // 显示忽略列表窗口
+        self.ignore_window
+            .show_ignore_window(ctx, &mut self.ignore_list);
+
+        // 显示已移动的文件夹窗口
+        self.relocation_window.show_relocation_window(ctx);
+
+        // 显示按时间清理窗口
+        self.age_cleanup.show_age_window(ctx);
+
+        // 显示备份后删除窗口，删除成功后从列表中移除
+        self.archive_module.show_archive_window(ctx);
+        if let Some(folder) = self.archive_module.take_deleted() {
+            self.folder_data.retain(|(name, _)| *name != folder);
+        }
+
+        // 显示从备份还原窗口，还原到当前根目录时加入列表
+        self.restore_module.show_restore_window(ctx);
+        if let Some((root, folder, size)) = self.restore_module.take_restored() {
+            if root == self.selected_appdata_folder {
+                match self
+                    .folder_data
+                    .iter_mut()
+                    .find(|(name, _)| *name == folder)
+                {
+                    Some(entry) => entry.1 = size,
+                    None => self.folder_data.push((folder, size)),
+                }
+            }
+        }
+
+        // 显示清理内容窗口，并同步清理后的文件夹大小
+        self.content_cleaner.show_clean_window(ctx);
+        if let Some((folder, freed)) = self.content_cleaner.take_freed() {
+            if let Some((_, size)) = self
+                .folder_data
+                .iter_mut()
+                .find(|(name, _)| *name == folder)
+            {
+                *size = size.saturating_sub(freed);
+            }
+        }
+    }
+
+    // 只在退出时保存窗口大小，避免调整大小时反复写入设置文件
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if let Some(window_size) = self.window_size {
+            if window_size != self.settings.window_size {
+                self.settings.window_size = window_size;
+                settings::save_settings(&self.settings);
+            }
+        }
     }
 }
@@ -9,6 +9,19 @@ pub fn format_size(size: u64) -> String {
     format!("{:.2} {}", size, UNITS[unit])
 }
 
+/// 带千位分隔符的数量，例如 1,204
+pub fn format_count(count: u64) -> String {
+    let digits = count.to_string();
+    let mut result = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, ch) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            result.push(',');
+        }
+        result.push(ch);
+    }
+    result
+}
+
 //use std::env;
 
 use dirs_next as dirs;
@@ -23,25 +36,350 @@ pub fn get_appdata_dir(folder_type: &str) -> Option<PathBuf> {
     }
 }
 
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use sha2::{Digest, Sha256};
 
+// 分块计算哈希时每块的大小，同时也是进度更新和检查取消的间隔
+const HASH_BUFFER_SIZE: usize = 256 * 1024;
+
 pub fn hash_file(path: &Path) -> Result<String, std::io::Error> {
-    let mut file = fs::File::open(path)?;
+    hash_reader(fs::File::open(path)?)
+}
+
+/// 计算任意数据流的 SHA-256，例如压缩包中的条目
+pub fn hash_reader(mut reader: impl std::io::Read) -> Result<String, std::io::Error> {
     let mut hasher = Sha256::new();
-    std::io::copy(&mut file, &mut hasher)?;
+    std::io::copy(&mut reader, &mut hasher)?;
     Ok(format!("{:x}", hasher.finalize()))
 }
 
-pub fn compare_dirs_hash(source: &Path, target: &Path) -> Result<bool, std::io::Error> {
-    let source_hashes: Vec<_> = fs::read_dir(source)?
-        .map(|entry| hash_file(&entry?.path()))
-        .collect::<Result<_, _>>()?;
-    let target_hashes: Vec<_> = fs::read_dir(target)?
-        .map(|entry| hash_file(&entry?.path()))
-        .collect::<Result<_, _>>()?;
+/// 分块计算数据流的 SHA-256，每读取一块调用一次 `on_chunk`，可以同时写入别处或更新进度。
+/// `cancel_flag` 置位后在下一块之前停止，返回 Ok(None)
+pub fn hash_reader_with_progress(
+    mut reader: impl std::io::Read,
+    cancel_flag: &AtomicBool,
+    mut on_chunk: impl FnMut(&[u8]) -> std::io::Result<()>,
+) -> std::io::Result<Option<String>> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; HASH_BUFFER_SIZE];
+    loop {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        on_chunk(&buffer[..read])?;
+        hasher.update(&buffer[..read]);
+    }
+    Ok(Some(format!("{:x}", hasher.finalize())))
+}
+
+// 路径本身或最近一个已存在的上级目录，目标路径可能还未创建
+fn nearest_existing(path: &Path) -> Option<PathBuf> {
+    let path = std::path::absolute(path).ok()?;
+    path.ancestors().find(|p| p.exists()).map(Path::to_path_buf)
+}
 
-    Ok(source_hashes == target_hashes)
+/// 路径所在磁盘对当前用户可用的空间，无法获取时返回 None
+pub fn available_space(path: &Path) -> Option<u64> {
+    let path = nearest_existing(path)?;
+    available_space_impl(&path)
+}
+
+#[cfg(unix)]
+fn available_space_impl(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
 }
 
+#[cfg(windows)]
+fn available_space_impl(path: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut free = 0u64;
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut free,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    (ok != 0).then_some(free)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn available_space_impl(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// 两个路径是否位于同一个卷上，同一个卷内可以直接重命名而不必复制
+pub fn same_volume(a: &Path, b: &Path) -> bool {
+    match (nearest_existing(a), nearest_existing(b)) {
+        (Some(a), Some(b)) => same_volume_impl(&a, &b),
+        _ => false,
+    }
+}
+
+#[cfg(unix)]
+fn same_volume_impl(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev(),
+        _ => false,
+    }
+}
+
+// 比较卷的挂载路径，挂载到文件夹的卷也能正确区分
+#[cfg(windows)]
+fn same_volume_impl(a: &Path, b: &Path) -> bool {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetVolumePathNameW;
+
+    let volume = |path: &Path| -> Option<String> {
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        let mut buffer = [0u16; 1024];
+        let ok =
+            unsafe { GetVolumePathNameW(wide.as_ptr(), buffer.as_mut_ptr(), buffer.len() as u32) };
+        if ok == 0 {
+            return None;
+        }
+        let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        Some(String::from_utf16_lossy(&buffer[..len]).to_lowercase())
+    };
+    matches!((volume(a), volume(b)), (Some(a), Some(b)) if a == b)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn same_volume_impl(_a: &Path, _b: &Path) -> bool {
+    false
+}
+
+/// 两个目录树之间的差异
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeMismatch {
+    Missing(PathBuf), // 源目录中有，目标目录中没有
+    Extra(PathBuf),   // 目标目录中多出来的
+    SizeDiffers {
+        path: PathBuf,
+        source: u64,
+        target: u64,
+    },
+    HashDiffers(PathBuf),
+    LinkDiffers(PathBuf), // 符号链接指向的路径不同
+    TypeDiffers {
+        path: PathBuf,
+        source: &'static str, // 条目类型，如 "目录"、"文件"
+        target: &'static str,
+    },
+}
+
+impl std::fmt::Display for TreeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TreeMismatch::Missing(path) => write!(f, "{}（目标中缺失）", path.display()),
+            TreeMismatch::Extra(path) => write!(f, "{}（目标中多出）", path.display()),
+            TreeMismatch::SizeDiffers {
+                path,
+                source,
+                target,
+            } => write!(
+                f,
+                "{}（大小不同: {} / {}）",
+                path.display(),
+                format_size(*source),
+                format_size(*target)
+            ),
+            TreeMismatch::HashDiffers(path) => write!(f, "{}（内容不同）", path.display()),
+            TreeMismatch::LinkDiffers(path) => write!(f, "{}（链接目标不同）", path.display()),
+            TreeMismatch::TypeDiffers {
+                path,
+                source,
+                target,
+            } => write!(f, "{}（类型不同: {} / {}）", path.display(), source, target),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TreeEntry {
+    Dir,
+    File(u64),
+    Link(PathBuf),
+    Special, // 管道、套接字等，只比较是否存在
+}
+
+impl TreeEntry {
+    fn kind(&self) -> &'static str {
+        match self {
+            TreeEntry::Dir => "目录",
+            TreeEntry::File(_) => "文件",
+            TreeEntry::Link(_) => "符号链接",
+            TreeEntry::Special => "特殊文件",
+        }
+    }
+}
+
+// 目录树中所有条目的相对路径。符号链接不跟随，只记录它指向的路径
+fn list_tree(root: &Path) -> Result<BTreeMap<PathBuf, TreeEntry>, std::io::Error> {
+    let mut entries = BTreeMap::new();
+    for entry in walkdir::WalkDir::new(root).min_depth(1) {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        let file_type = entry.file_type();
+        let item = if file_type.is_symlink() {
+            TreeEntry::Link(fs::read_link(entry.path())?)
+        } else if file_type.is_dir() {
+            TreeEntry::Dir
+        } else if !file_type.is_file() {
+            TreeEntry::Special
+        } else {
+            TreeEntry::File(entry.metadata()?.len())
+        };
+        entries.insert(relative.to_path_buf(), item);
+    }
+    Ok(entries)
+}
+
+/// 递归比较两个目录树的相对路径、文件大小和 SHA-256，与遍历顺序无关。
+/// 路径和大小都一致的文件才会计算哈希，由多个线程并行完成；符号链接只比较指向的路径。
+/// 返回所有差异，为空表示两个目录完全一致
+pub fn compare_dirs_hash(
+    source: &Path,
+    target: &Path,
+    cancel_flag: &AtomicBool,
+) -> Result<Vec<TreeMismatch>, std::io::Error> {
+    let source_entries = list_tree(source)?;
+    let target_entries = list_tree(target)?;
+
+    let mut mismatches = Vec::new();
+    let mut to_hash = Vec::new();
+    for (path, source_entry) in &source_entries {
+        match (source_entry, target_entries.get(path)) {
+            (_, None) => mismatches.push(TreeMismatch::Missing(path.clone())),
+            (TreeEntry::File(source), Some(TreeEntry::File(target))) if source != target => {
+                mismatches.push(TreeMismatch::SizeDiffers {
+                    path: path.clone(),
+                    source: *source,
+                    target: *target,
+                })
+            }
+            (TreeEntry::File(_), Some(TreeEntry::File(_))) => to_hash.push(path),
+            (TreeEntry::Dir, Some(TreeEntry::Dir)) => {}
+            (TreeEntry::Special, Some(TreeEntry::Special)) => {}
+            (TreeEntry::Link(source), Some(TreeEntry::Link(target))) => {
+                if source != target {
+                    mismatches.push(TreeMismatch::LinkDiffers(path.clone()));
+                }
+            }
+            // 条目类型不同，例如一边是目录、一边是文件
+            (source, Some(target)) => mismatches.push(TreeMismatch::TypeDiffers {
+                path: path.clone(),
+                source: source.kind(),
+                target: target.kind(),
+            }),
+        }
+    }
+    mismatches.extend(
+        target_entries
+            .keys()
+            .filter(|path| !source_entries.contains_key(*path))
+            .map(|path| TreeMismatch::Extra(path.clone())),
+    );
+
+    // 多个线程从同一个队列中取文件计算哈希
+    let next = AtomicUsize::new(0);
+    let workers = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(to_hash.len())
+        .max(1);
+    let results: Vec<Result<Vec<TreeMismatch>, std::io::Error>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut differs = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(path) = to_hash.get(index) else {
+                            return Ok(differs);
+                        };
+                        if cancel_flag.load(Ordering::Relaxed) {
+                            return Err(std::io::Error::new(
+                                std::io::ErrorKind::Interrupted,
+                                "已取消校验",
+                            ));
+                        }
+                        if hash_file(&source.join(path))? != hash_file(&target.join(path))? {
+                            differs.push(TreeMismatch::HashDiffers((*path).clone()));
+                        }
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("哈希线程异常退出"))
+            .collect()
+    });
+    for result in results {
+        mismatches.extend(result?);
+    }
+
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_dirs_hash_reports_every_kind_of_difference() {
+        let base = std::env::temp_dir().join(format!("adc_compare_{}", std::process::id()));
+        let (source, target) = (base.join("source"), base.join("target"));
+        for dir in [&source, &target] {
+            fs::create_dir_all(dir.join("sub")).unwrap();
+            fs::write(dir.join("same.txt"), "same").unwrap();
+            fs::write(dir.join("sub/differs.txt"), "aaaa").unwrap();
+        }
+        fs::write(target.join("sub/differs.txt"), "bbbb").unwrap();
+        fs::write(source.join("missing.txt"), "only in source").unwrap();
+        fs::write(target.join("extra.txt"), "only in target").unwrap();
+        fs::write(source.join("kind"), "file").unwrap();
+        fs::create_dir(target.join("kind")).unwrap();
+
+        let mut mismatches = compare_dirs_hash(&source, &target, &AtomicBool::new(false)).unwrap();
+        mismatches.sort_by_key(|m| m.to_string());
+        assert_eq!(
+            mismatches,
+            [
+                TreeMismatch::Extra(PathBuf::from("extra.txt")),
+                TreeMismatch::TypeDiffers {
+                    path: PathBuf::from("kind"),
+                    source: "文件",
+                    target: "目录",
+                },
+                TreeMismatch::Missing(PathBuf::from("missing.txt")),
+                TreeMismatch::HashDiffers(PathBuf::from("sub/differs.txt")),
+            ]
+        );
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
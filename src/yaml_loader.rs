@@ -1,44 +1,106 @@
+use crate::rule_sources::{self, RuleSource};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
-#[derive(Debug, Serialize, Deserialize)]
+pub const DESCRIPTIONS_FILE: &str = "folders_description.yaml";
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FolderDescriptions {
-    pub Roaming: HashMap<String, String>,
-    pub Local: HashMap<String, String>,
-    pub LocalLow: HashMap<String, String>,
+    #[serde(rename = "Roaming")]
+    pub roaming: HashMap<String, String>,
+    #[serde(rename = "Local")]
+    pub local: HashMap<String, String>,
+    #[serde(rename = "LocalLow")]
+    pub local_low: HashMap<String, String>,
 }
 
 impl FolderDescriptions {
-    pub fn load_from_yaml(file_path: &str) -> Result<Self, String> {
-        let path = Path::new(file_path);
+    pub fn parse(content: &str) -> Result<Self, String> {
+        serde_yaml::from_str(content).map_err(|e| format!("解析 YAML 文件失败: {}", e))
+    }
+
+    pub fn load_from_yaml(path: &Path) -> Result<Self, String> {
         if !path.exists() {
             return Err("YAML 文件未找到".to_string());
         }
 
         let content = fs::read_to_string(path).map_err(|e| format!("读取 YAML 文件失败: {}", e))?;
 
-        let descriptions: FolderDescriptions =
-            serde_yaml::from_str(&content).map_err(|e| format!("解析 YAML 文件失败: {}", e))?;
-
-        Ok(descriptions)
+        Self::parse(&content)
     }
 
     pub fn get_description(&self, folder_name: &str, folder_type: &str) -> Option<String> {
         match folder_type {
-            "Roaming" => self.Roaming.get(folder_name).cloned(),
-            "Local" => self.Local.get(folder_name).cloned(),
-            "LocalLow" => self.LocalLow.get(folder_name).cloned(),
+            "Roaming" => self.roaming.get(folder_name).cloned(),
+            "Local" => self.local.get(folder_name).cloned(),
+            "LocalLow" => self.local_low.get(folder_name).cloned(),
             _ => None,
         }
     }
+
+    fn roots(&self) -> [(&str, &HashMap<String, String>); 3] {
+        [
+            ("Roaming", &self.roaming),
+            ("Local", &self.local),
+            ("LocalLow", &self.local_low),
+        ]
+    }
+
+    /// 用另一份规则覆盖同名文件夹的描述
+    pub fn merge(&mut self, other: FolderDescriptions) {
+        self.roaming.extend(other.roaming);
+        self.local.extend(other.local);
+        self.local_low.extend(other.local_low);
+    }
+
+    /// 与旧规则相比新增或修改的描述条数
+    pub fn count_changes(&self, old: &FolderDescriptions) -> usize {
+        self.roots()
+            .into_iter()
+            .map(|(root, entries)| {
+                entries
+                    .iter()
+                    .filter(|(folder, desc)| {
+                        old.get_description(folder, root).as_ref() != Some(*desc)
+                    })
+                    .count()
+            })
+            .sum()
+    }
+}
+
+/// 按优先级从低到高合并订阅源的缓存，优先级高的覆盖优先级低的
+pub fn merge_cached_sources(sources: &[RuleSource], cache_dir: &Path) -> FolderDescriptions {
+    let mut merged = FolderDescriptions::default();
+    for (source, content) in rule_sources::cached_rules(sources, cache_dir) {
+        match FolderDescriptions::parse(&content) {
+            Ok(descriptions) => merged.merge(descriptions),
+            Err(e) => {
+                crate::logger::log_error(&format!("订阅源 {} 的缓存无效: {}", source.name, e))
+            }
+        }
+    }
+    merged
 }
 
-// 新增函数，用于加载文件夹描述
-pub fn load_folder_descriptions(file_path: &str, yaml_error_logged: &mut bool) -> Option<FolderDescriptions> {
+// 新增函数，用于加载文件夹描述。先合并订阅源，配置目录中的文件优先级最高
+pub fn load_folder_descriptions(
+    file_path: &Path,
+    yaml_error_logged: &mut bool,
+) -> Option<FolderDescriptions> {
+    let sources = rule_sources::load_rule_sources();
+    let mut descriptions = merge_cached_sources(&sources, &rule_sources::cache_dir());
+    let has_subscriptions = sources.iter().any(|s| s.enabled);
+
     match FolderDescriptions::load_from_yaml(file_path) {
-        Ok(descriptions) => Some(descriptions),
+        Ok(local) => {
+            descriptions.merge(local);
+            Some(descriptions)
+        }
+        Err(_) if has_subscriptions => Some(descriptions),
         Err(e) => {
             if !*yaml_error_logged {
                 eprintln!("加载 YAML 文件失败: {}", e);
@@ -48,4 +110,4 @@ pub fn load_folder_descriptions(file_path: &str, yaml_error_logged: &mut bool) -
             None
         }
     }
-}
\ No newline at end of file
+}